@@ -0,0 +1,481 @@
+use crate::integrator::{self, Bsdf, Integrator};
+use crate::pixel::Colour;
+use crate::prop::Shading;
+use crate::rng::{Sampler, Uniform};
+use crate::scene::{Projection, Scene};
+use crate::vector::{Ray, Vector};
+
+// Veach's bidirectional path tracer. Light paths joined straight to the eye
+// land on other pixels, so it renders whole frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bdpt {
+    pub max_depth: usize,
+}
+
+#[derive(Clone, Copy)]
+pub struct View<'a> {
+    pub scene: &'a Scene,
+    pub projection: &'a dyn Projection,
+    pub dims: [usize; 2],
+    pub time: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Camera,
+    Light,
+    Surface {
+        bsdf: Bsdf,
+        wo: Vector,
+        emissive: Colour,
+    },
+}
+
+// Points (the eye and point lights) have a zero normal; surface normals face
+// the direction the path arrived from.
+#[derive(Clone, Copy, Debug)]
+pub struct Vertex {
+    kind: Kind,
+    p: Vector,
+    n: Vector,
+    beta: Colour,
+    pdf_fwd: f64,
+    pdf_rev: f64,
+    // sampled without a density, so no other strategy can reach it
+    delta: bool,
+}
+
+struct Pinned(Ray);
+
+impl Projection for Pinned {
+    fn ray(&self, _: [f64; 2], _: [usize; 2]) -> Ray {
+        self.0
+    }
+}
+
+impl Vertex {
+    const fn new(kind: Kind, p: Vector, n: Vector, beta: Colour) -> Self {
+        Self {
+            kind,
+            p,
+            n,
+            beta,
+            pdf_fwd: 0.,
+            pdf_rev: 0.,
+            delta: false,
+        }
+    }
+    fn on_surface(&self) -> bool {
+        self.n.sq() > 0.
+    }
+    fn connectible(&self) -> bool {
+        !self.delta
+    }
+    fn is_delta_light(&self) -> bool {
+        matches!(self.kind, Kind::Light) && !self.on_surface()
+    }
+    fn cos(&self, other: &Self) -> f64 {
+        if self.on_surface() {
+            (self.n * (other.p - self.p).norm()).abs()
+        } else {
+            1.
+        }
+    }
+    fn area_pdf(&self, pdf: f64, next: &Self) -> f64 {
+        let w = next.p - self.p;
+        let d2 = w.sq();
+        if d2 == 0. {
+            return 0.;
+        }
+        pdf / d2 * next.cos(self)
+    }
+    fn f(&self, next: &Self) -> Colour {
+        match self.kind {
+            Kind::Surface { bsdf, wo, .. } => bsdf.eval(self.n, wo, (next.p - self.p).norm()),
+            _ => Colour::BLACK,
+        }
+    }
+    fn pdf(&self, view: &View, prev: Option<&Self>, next: &Self) -> f64 {
+        let pdf = match self.kind {
+            Kind::Light => return self.pdf_light(view, next),
+            Kind::Camera => view
+                .projection
+                .project(next.p, view.dims)
+                .map_or(0., |projected| projected.pdf),
+            Kind::Surface { bsdf, .. } => {
+                let Some(prev) = prev else {
+                    return 0.;
+                };
+                let (wp, wn) = ((prev.p - self.p).norm(), (next.p - self.p).norm());
+                let n = if self.n * wp < 0. { -self.n } else { self.n };
+                bsdf.pdf(n, wp, wn)
+            }
+        };
+        self.area_pdf(pdf, next)
+    }
+    fn pdf_light(&self, view: &View, next: &Self) -> f64 {
+        let light = &view.scene.light;
+        self.area_pdf(light.emit_pdf_dir(self.n, (next.p - self.p).norm()), next)
+    }
+}
+
+// Whether nothing, the light included, lies strictly between the points.
+fn visible(view: &View, from: Vector, to: Vector) -> bool {
+    let scene = view.scene;
+    let disp = to - from;
+    let distance = disp.abs();
+    !scene.occluded(from, to, view.time)
+        && scene
+            .light
+            .hit(Ray::new(from, disp / distance), scene.eps)
+            .is_none_or(|t| t >= distance - scene.eps)
+}
+
+// The light is opaque: paths from the eye end on it, and paths from the light
+// die there.
+fn walk(
+    view: &View,
+    mut ray: Ray,
+    mut beta: Colour,
+    mut pdf: f64,
+    sampler: &mut impl Uniform,
+    vertices: usize,
+    path: &mut Vec<Vertex>,
+) {
+    let scene = view.scene;
+    let from_eye = matches!(path[0].kind, Kind::Camera);
+    while path.len() < vertices {
+        let prev = path.len() - 1;
+        let hit = scene.hit(ray);
+        if let Some(t) = scene.light.hit(ray, scene.eps)
+            && hit.as_ref().is_none_or(|hit| hit.t() > t)
+        {
+            if !from_eye {
+                return;
+            }
+            let p = ray.at(t);
+            let mut vertex = Vertex::new(Kind::Light, p, (p - scene.light.position).norm(), beta);
+            vertex.pdf_fwd = path[prev].area_pdf(pdf, &vertex);
+            path.push(vertex);
+            return;
+        }
+        let Some(hit) = hit else {
+            return;
+        };
+
+        let wo = -ray.dir.norm();
+        let n = hit.shading_normal();
+        let n = if hit.normal * wo < 0. { -n } else { n };
+        let emissive = match hit.shading {
+            Shading::Pbr(pbr) => pbr.emissive,
+            _ => Colour::BLACK,
+        };
+        let bsdf = Bsdf::new(&hit);
+        let mut vertex = Vertex::new(Kind::Surface { bsdf, wo, emissive }, hit.position, n, beta);
+        vertex.pdf_fwd = path[prev].area_pdf(pdf, &vertex);
+        vertex.delta = bsdf.is_delta();
+        path.push(vertex);
+        if path.len() >= vertices {
+            return;
+        }
+
+        let Some(scatter) = bsdf.scatter(n, wo, sampler) else {
+            return;
+        };
+        beta *= scatter.weight;
+        // specular bounces can't be found any other way, so their pdfs are
+        // left out of the weights
+        let rev = if vertex.delta {
+            pdf = 0.;
+            0.
+        } else {
+            pdf = scatter.pdf;
+            bsdf.pdf(n, scatter.dir, wo)
+        };
+        path[prev].pdf_rev = vertex.area_pdf(rev, &path[prev]);
+        ray = integrator::next_ray(&hit, &bsdf, scatter.dir);
+    }
+}
+
+pub fn camera_path(
+    view: &View,
+    pixel: [f64; 2],
+    sampler: &mut impl Uniform,
+    vertices: usize,
+) -> Vec<Vertex> {
+    let ray = view.projection.ray(pixel, view.dims).with_time(view.time);
+    let projected = view.projection.project(ray.at(1.), view.dims);
+    let mut eye = Vertex::new(Kind::Camera, ray.eye, Vector::default(), Colour::WHITE);
+    // light paths can only be joined to cameras that can project onto the image
+    eye.delta = projected.is_none();
+    let mut path = vec![eye];
+    let pdf = projected.map_or(0., |projected| projected.pdf);
+    walk(view, ray, Colour::WHITE, pdf, sampler, vertices, &mut path);
+    path
+}
+
+pub fn light_path(view: &View, sampler: &mut impl Uniform, vertices: usize) -> Vec<Vertex> {
+    if vertices == 0 {
+        return Vec::new();
+    }
+    let emission = view
+        .scene
+        .light
+        .emit(sampler.uniform_2d(), sampler.uniform_2d());
+    let beta = emission.radiance / emission.pdf_position;
+    let mut origin = Vertex::new(Kind::Light, emission.ray.eye, emission.normal, beta);
+    origin.pdf_fwd = emission.pdf_position;
+    let mut path = vec![origin];
+    if emission.pdf_dir > 0. {
+        let cos = if origin.on_surface() {
+            emission.normal * emission.ray.dir
+        } else {
+            1.
+        };
+        let beta = beta * (cos / emission.pdf_dir);
+        let ray = emission.ray.with_time(view.time);
+        walk(
+            view,
+            ray,
+            beta,
+            emission.pdf_dir,
+            sampler,
+            vertices,
+            &mut path,
+        );
+    }
+    path
+}
+
+// One vertex on either side is resampled: a point on the light, or the eye
+// seeing the end of the light path.
+pub fn connect(
+    view: &View,
+    light: &[Vertex],
+    camera: &[Vertex],
+    sampler: &mut impl Uniform,
+) -> Option<(Colour, Option<[f64; 2]>)> {
+    let scene = view.scene;
+    let (s, t) = (light.len(), camera.len());
+    let pt = camera.last()?;
+    if t > 1 && s > 0 && matches!(pt.kind, Kind::Light) {
+        return None;
+    }
+
+    let mut sampled = None;
+    let mut pixel = None;
+    let value = if s == 0 {
+        match pt.kind {
+            Kind::Light if pt.n * (camera[t - 2].p - pt.p) > 0. => pt.beta * scene.light.radiance(),
+            // only the eye's paths find emissive surfaces, so they need no weight
+            Kind::Surface { emissive, .. } if emissive.luminance() > 0. => {
+                return Some((pt.beta * emissive, None));
+            }
+            _ => return None,
+        }
+    } else if t == 1 {
+        let qs = &light[s - 1];
+        if !qs.connectible() {
+            return None;
+        }
+        let projected = view.projection.project(qs.p, view.dims)?;
+        if !visible(view, qs.p, projected.eye) {
+            return None;
+        }
+        // the importance times cos θ at the eye is the pdf of its ray
+        let d2 = (projected.eye - qs.p).sq();
+        let eye = Vertex::new(
+            Kind::Camera,
+            projected.eye,
+            Vector::default(),
+            projected.pdf / d2 * Colour::WHITE,
+        );
+        pixel = Some(projected.pixel);
+        sampled = Some(eye);
+        qs.beta * qs.f(&eye) * eye.beta * qs.cos(&eye)
+    } else if s == 1 {
+        if !pt.connectible() {
+            return None;
+        }
+        let light = &scene.light;
+        let sample = light.sample(pt.p, sampler.uniform_2d())?;
+        let p = pt.p + sample.distance * sample.dir;
+        let n = if light.radius > 0. {
+            (p - light.position).norm()
+        } else {
+            Vector::default()
+        };
+        let mut vertex = Vertex::new(Kind::Light, p, n, sample.value);
+        vertex.pdf_fwd = light.emit_pdf_position();
+        if !visible(view, pt.p, p) {
+            return None;
+        }
+        sampled = Some(vertex);
+        pt.beta * pt.f(&vertex) * vertex.beta * pt.cos(&vertex)
+    } else {
+        let qs = &light[s - 1];
+        if !qs.connectible() || !pt.connectible() {
+            return None;
+        }
+        let value = qs.beta * qs.f(pt) * pt.f(qs) * pt.beta;
+        if value.luminance() <= 0. || !visible(view, qs.p, pt.p) {
+            return None;
+        }
+        value * (qs.cos(pt) * pt.cos(qs) / (qs.p - pt.p).sq())
+    };
+
+    if value.luminance() <= 0. {
+        return None;
+    }
+    Some((value * weight(view, light, camera, sampled), pixel))
+}
+
+fn weight(view: &View, light: &[Vertex], camera: &[Vertex], sampled: Option<Vertex>) -> f64 {
+    let (s, t) = (light.len(), camera.len());
+    if s + t == 2 {
+        return 1.;
+    }
+    let (mut light, mut camera) = (light.to_vec(), camera.to_vec());
+    if let Some(vertex) = sampled {
+        if s == 1 {
+            light[0] = vertex;
+        } else {
+            camera[0] = vertex;
+        }
+    }
+
+    let pt = camera[t - 1];
+    let qs = s.checked_sub(1).map(|i| light[i]);
+    let rev = match qs {
+        Some(qs) => qs.pdf(view, (s > 1).then(|| &light[s - 2]), &pt),
+        None => view.scene.light.emit_pdf_position(),
+    };
+    camera[t - 1].pdf_rev = rev;
+    camera[t - 1].delta = false;
+    if t > 1 {
+        let rev = match qs {
+            Some(qs) => pt.pdf(view, Some(&qs), &camera[t - 2]),
+            None => pt.pdf_light(view, &camera[t - 2]),
+        };
+        camera[t - 2].pdf_rev = rev;
+    }
+    if let Some(qs) = qs {
+        light[s - 1].pdf_rev = pt.pdf(view, (t > 1).then(|| &camera[t - 2]), &qs);
+        light[s - 1].delta = false;
+        if s > 1 {
+            let rev = qs.pdf(view, Some(&pt), &light[s - 2]);
+            light[s - 2].pdf_rev = rev;
+        }
+    }
+
+    // zero pdfs come from points, which take part in every strategy alike
+    let remap = |pdf: f64| if pdf != 0. { pdf } else { 1. };
+    let mut sum = 0.;
+    let mut ratio = 1.;
+    for i in (1..t).rev() {
+        ratio *= remap(camera[i].pdf_rev) / remap(camera[i].pdf_fwd);
+        if !camera[i].delta && !camera[i - 1].delta {
+            sum += ratio * ratio;
+        }
+    }
+    ratio = 1.;
+    for i in (0..s).rev() {
+        ratio *= remap(light[i].pdf_rev) / remap(light[i].pdf_fwd);
+        let delta_light = match i {
+            0 => light[0].is_delta_light(),
+            _ => light[i - 1].delta,
+        };
+        if !light[i].delta && !delta_light {
+            sum += ratio * ratio;
+        }
+    }
+    1. / (1. + sum)
+}
+
+impl Bdpt {
+    pub const fn new(max_depth: usize) -> Self {
+        Self { max_depth }
+    }
+    fn trace(
+        &self,
+        view: &View,
+        pixel: [f64; 2],
+        sampler: &mut Sampler,
+        mut splat: impl FnMut([f64; 2], Colour),
+    ) -> Option<Colour> {
+        let camera = camera_path(view, pixel, sampler, self.max_depth + 2);
+        let light = light_path(view, sampler, self.max_depth + 1);
+        let mut radiance = Colour::BLACK;
+        for t in 1..=camera.len() {
+            for s in 0..=light.len() {
+                if s + t < 2 || s + t - 2 > self.max_depth || (s == 1 && t == 1) {
+                    continue;
+                }
+                match connect(view, &light[..s], &camera[..t], sampler) {
+                    Some((value, Some(pixel))) => splat(pixel, value),
+                    Some((value, None)) => radiance += value,
+                    None => {}
+                }
+            }
+        }
+        (camera.len() > 1).then_some(radiance)
+    }
+}
+
+impl Default for Bdpt {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl Integrator for Bdpt {
+    fn radiance(&self, scene: &Scene, ray: Ray, sampler: &mut Sampler) -> Option<Colour> {
+        let view = View {
+            scene,
+            projection: &Pinned(ray),
+            dims: [1, 1],
+            time: ray.time,
+        };
+        self.trace(&view, [0., 0.], sampler, |_, _| {})
+    }
+    fn render(
+        &self,
+        scene: &Scene,
+        projection: &dyn Projection,
+        dims @ [w, h]: [usize; 2],
+    ) -> Option<Vec<Option<Colour>>> {
+        let [open, close] = projection.shutter();
+        let samples = scene.samples.max(1);
+        let mut film = vec![Colour::BLACK; w * h];
+        let mut seen = vec![false; w * h];
+
+        for (y, x) in (0..h).flat_map(|y| (0..w).map(move |x| (y, x))) {
+            for i in 0..samples {
+                let view = View {
+                    scene,
+                    projection,
+                    dims,
+                    time: open + (close - open) * (i as f64 + 0.5) / samples as f64,
+                };
+                let mut sampler = scene.sampler([x, y], i);
+                let [u, v] = sampler.uniform_2d();
+                let pixel = [x as f64 + u - 0.5, y as f64 + v - 0.5];
+                let radiance = self.trace(&view, pixel, &mut sampler, |pixel, value| {
+                    let [x, y] = pixel.map(|c| (c + 0.5) as usize);
+                    film[y * w + x] += value;
+                    seen[y * w + x] = true;
+                });
+                if let Some(radiance) = radiance {
+                    film[y * w + x] += radiance;
+                    seen[y * w + x] = true;
+                }
+            }
+        }
+        Some(
+            film.into_iter()
+                .zip(seen)
+                .map(|(colour, seen)| seen.then_some(colour / samples as f64))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,143 @@
+use crate::vector::{Aabb, Ray, Vector};
+
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Aabb,
+    // leaves hold `count` indices from `start`; interior nodes have their left
+    // child next in the array and their right child at `start`
+    start: usize,
+    count: usize,
+}
+
+impl Bvh {
+    pub const LEAF_SIZE: usize = 4;
+
+    pub fn new(bounds: &[Aabb]) -> Self {
+        Self::from_fn(bounds.len(), Self::LEAF_SIZE, |i| bounds[i])
+    }
+    // Builds over `len` primitives without collecting their bounds, which
+    // keeps very large sets such as point clouds affordable.
+    pub fn from_fn(len: usize, leaf_size: usize, bounds: impl Fn(usize) -> Aabb) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * len / leaf_size.max(1) + 1),
+            indices: (0..len).collect(),
+        };
+        if len > 0 {
+            bvh.build(&bounds, leaf_size.max(1), 0, len);
+        }
+        bvh
+    }
+    fn build(
+        &mut self,
+        bounds: &impl Fn(usize) -> Aabb,
+        leaf_size: usize,
+        start: usize,
+        end: usize,
+    ) -> usize {
+        let slice = &mut self.indices[start..end];
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: slice
+                .iter()
+                .fold(Aabb::EMPTY, |acc, &i| acc.union(bounds(i))),
+            start,
+            count: end - start,
+        });
+        if end - start <= leaf_size {
+            return node;
+        }
+
+        let spread = slice
+            .iter()
+            .fold(Aabb::EMPTY, |acc, &i| acc.include(bounds(i).centre()))
+            .extent();
+        let axis = (0..3)
+            .max_by(|&a, &b| spread[a].total_cmp(&spread[b]))
+            .unwrap();
+        let mid = (end - start) / 2;
+        slice.select_nth_unstable_by(mid, |&a, &b| {
+            bounds(a).centre()[axis].total_cmp(&bounds(b).centre()[axis])
+        });
+
+        self.build(bounds, leaf_size, start, start + mid);
+        let right = self.build(bounds, leaf_size, start + mid, end);
+        self.nodes[node].start = right;
+        self.nodes[node].count = 0;
+        node
+    }
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |node| node.bounds)
+    }
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+    // The first primitive whose bounds contain `p` that `found` accepts.
+    pub fn find<T>(&self, p: Vector, mut found: impl FnMut(usize) -> Option<T>) -> Option<T> {
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = self.nodes.get(index)?;
+            if !node.bounds.contains(p) {
+                continue;
+            }
+            if node.count > 0 {
+                let indices = &self.indices[node.start..node.start + node.count];
+                if let Some(found) = indices.iter().find_map(|&i| found(i)) {
+                    return Some(found);
+                }
+            } else {
+                stack.extend([node.start, index + 1]);
+            }
+        }
+        None
+    }
+    // Finds the nearest hit; `hit` is given a primitive index and the current
+    // nearest t, and returns the primitive's own nearest t below it.
+    pub fn nearest<T>(
+        &self,
+        ray: Ray,
+        eps: f64,
+        mut hit: impl FnMut(usize, f64) -> Option<(f64, T)>,
+    ) -> Option<(f64, T)> {
+        let mut best: Option<(f64, T)> = None;
+        let [t0, _] = self.nodes.first()?.bounds.hit(ray, eps)?;
+
+        let mut stack = vec![(0, t0)];
+        while let Some((index, entry)) = stack.pop() {
+            let limit = best.as_ref().map_or(f64::INFINITY, |(t, _)| *t);
+            if entry > limit {
+                continue;
+            }
+            let node = self.nodes[index];
+            if node.count > 0 {
+                for &i in &self.indices[node.start..node.start + node.count] {
+                    let limit = best.as_ref().map_or(f64::INFINITY, |(t, _)| *t);
+                    if let Some(found) = hit(i, limit)
+                        && found.0 < limit
+                    {
+                        best = Some(found);
+                    }
+                }
+            } else {
+                let children = [index + 1, node.start]
+                    .map(|child| (child, self.nodes[child].bounds.hit(ray, eps)));
+                let mut children = children
+                    .into_iter()
+                    .filter_map(|(child, range)| Some((child, range?[0])))
+                    .collect::<Vec<_>>();
+                // push the farther child first so the nearer one is popped next
+                children.sort_by(|a, b| b.1.total_cmp(&a.1));
+                stack.extend(children);
+            }
+        }
+        best
+    }
+}
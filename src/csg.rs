@@ -0,0 +1,95 @@
+use crate::prop::{HitRecord, Prop};
+use crate::vector::{Aabb, Ray};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Csg<A: Prop, B: Prop> {
+    pub op: Operation,
+    pub left: A,
+    pub right: B,
+}
+
+impl Operation {
+    pub const fn contains(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+}
+
+impl<A: Prop, B: Prop> Csg<A, B> {
+    pub const fn new(op: Operation, left: A, right: B) -> Self {
+        Self { op, left, right }
+    }
+    pub const fn union(left: A, right: B) -> Self {
+        Self::new(Operation::Union, left, right)
+    }
+    pub const fn intersection(left: A, right: B) -> Self {
+        Self::new(Operation::Intersection, left, right)
+    }
+    pub const fn difference(left: A, right: B) -> Self {
+        Self::new(Operation::Difference, left, right)
+    }
+}
+
+impl<A: Prop, B: Prop> Prop for Csg<A, B> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        let left = self.left.raycast_all(ray, eps);
+        let right = self.right.raycast_all(ray, eps);
+
+        let mut in_left = left.first().is_some_and(|h| !h.entering());
+        let mut in_right = right.first().is_some_and(|h| !h.entering());
+        let mut inside = self.op.contains(in_left, in_right);
+
+        let mut hits = Vec::new();
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+
+        loop {
+            let from_left = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => l.distance <= r.distance,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let mut hit = if from_left {
+                let hit = left.next().unwrap();
+                in_left = hit.entering();
+                hit
+            } else {
+                let hit = right.next().unwrap();
+                in_right = hit.entering();
+                hit
+            };
+
+            let now = self.op.contains(in_left, in_right);
+            if now != inside {
+                if !from_left && self.op == Operation::Difference {
+                    hit.normal = -hit.normal;
+                    hit.bumped = hit.bumped.map(|n| -n);
+                }
+                hits.push(hit);
+                inside = now;
+            }
+        }
+        hits
+    }
+    fn bounds(&self) -> Aabb {
+        match self.op {
+            Operation::Union => self.left.bounds().union(self.right.bounds()),
+            Operation::Intersection => self.left.bounds().intersection(self.right.bounds()),
+            Operation::Difference => self.left.bounds(),
+        }
+    }
+}
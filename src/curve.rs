@@ -0,0 +1,212 @@
+use crate::bvh::Bvh;
+use crate::prop::{HitRecord, Material, Prop, Shading};
+use crate::vector::{Aabb, Ray, Vector};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Curve {
+    pub points: [Vector; 4],
+    pub width: [f64; 2],
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CurveKind {
+    Ribbon,
+    Cylinder,
+}
+
+#[derive(Clone, Debug)]
+pub struct Curves {
+    curves: Vec<Curve>,
+    bvh: Bvh,
+    pub kind: CurveKind,
+    pub hair: bool,
+    pub material: Material,
+}
+
+fn eval([p0, p1, p2, p3]: [Vector; 4], u: f64) -> Vector {
+    let s = 1. - u;
+    s * s * s * p0 + 3. * s * s * u * p1 + 3. * s * u * u * p2 + u * u * u * p3
+}
+
+fn derivative([p0, p1, p2, p3]: [Vector; 4], u: f64) -> Vector {
+    let s = 1. - u;
+    3. * (s * s * (p1 - p0) + 2. * s * u * (p2 - p1) + u * u * (p3 - p2))
+}
+
+fn split([p0, p1, p2, p3]: [Vector; 4]) -> [[Vector; 4]; 2] {
+    let mid = |a: Vector, b: Vector| 0.5 * (a + b);
+    let (a, b, c) = (mid(p0, p1), mid(p1, p2), mid(p2, p3));
+    let (d, e) = (mid(a, b), mid(b, c));
+    let f = mid(d, e);
+    [[p0, a, d, f], [f, e, c, p3]]
+}
+
+impl Curve {
+    pub fn width_at(&self, u: f64) -> f64 {
+        self.width[0] + u * (self.width[1] - self.width[0])
+    }
+    pub fn point(&self, u: f64) -> Vector {
+        eval(self.points, u)
+    }
+    pub fn tangent(&self, u: f64) -> Vector {
+        let d = derivative(self.points, u);
+        if d.sq() > 0. {
+            d.norm()
+        } else {
+            (self.points[3] - self.points[0]).norm()
+        }
+    }
+    pub fn bounds(&self) -> Aabb {
+        let half = 0.5 * self.width[0].max(self.width[1]);
+        let pad = Vector::new(half, half, half);
+        let hull = self.points.into_iter().fold(Aabb::EMPTY, Aabb::include);
+        Aabb::new(hull.min - pad, hull.max + pad)
+    }
+    // Converts a uniform cubic B-spline through a strand's control points into
+    // Bézier segments, with the width tapering linearly along the strand.
+    pub fn strand(points: &[Vector], width: [f64; 2]) -> Vec<Self> {
+        let n = points.len().saturating_sub(3);
+        (0..n)
+            .map(|i| {
+                let [a, b, c, d] = [points[i], points[i + 1], points[i + 2], points[i + 3]];
+                let lerp = |s: f64| width[0] + s / n as f64 * (width[1] - width[0]);
+                Self {
+                    points: [
+                        (a + 4. * b + c) / 6.,
+                        (2. * b + c) / 3.,
+                        (b + 2. * c) / 3.,
+                        (b + 4. * c + d) / 6.,
+                    ],
+                    width: [lerp(i as f64), lerp((i + 1) as f64)],
+                }
+            })
+            .collect()
+    }
+    // Subdivision depth from the control polygon's flatness, after Nakamaru
+    // and Ohno as used in pbrt.
+    fn depth(&self) -> usize {
+        let [p0, p1, p2, p3] = self.points;
+        let flatness = [p0 - 2. * p1 + p2, p1 - 2. * p2 + p3]
+            .into_iter()
+            .map(|d| d.x.abs().max(d.y.abs()).max(d.z.abs()))
+            .fold(0., f64::max);
+        let eps = 0.05 * self.width[0].max(self.width[1]);
+        let r = (std::f64::consts::SQRT_2 * 6. * flatness / (8. * eps)).log2() / 2.;
+        if r.is_finite() {
+            r.clamp(0., 10.) as usize
+        } else {
+            0
+        }
+    }
+    fn recurse(
+        &self,
+        points: [Vector; 4],
+        [u0, u1]: [f64; 2],
+        depth: usize,
+        [zmin, zmax]: [f64; 2],
+    ) -> Option<(f64, f64)> {
+        let half = 0.5 * self.width_at(u0).max(self.width_at(u1));
+        let hull = points.into_iter().fold(Aabb::EMPTY, Aabb::include);
+        if hull.min.x - half > 0.
+            || hull.max.x + half < 0.
+            || hull.min.y - half > 0.
+            || hull.max.y + half < 0.
+            || hull.min.z - half > zmax
+            || hull.max.z + half < zmin
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let um = 0.5 * (u0 + u1);
+            let [left, right] = split(points);
+            let near = self.recurse(left, [u0, um], depth - 1, [zmin, zmax]);
+            let zmax = near.map_or(zmax, |(z, _)| z);
+            let far = self.recurse(right, [um, u1], depth - 1, [zmin, zmax]);
+            return far.or(near);
+        }
+
+        let [p0, p1, p2, p3] = points;
+        let xy = |v: Vector| Vector::new(v.x, v.y, 0.);
+        // reject rays outside the planes through the end points perpendicular to the end tangents
+        if xy(p1 - p0) * -xy(p0) < 0. || xy(p2 - p3) * -xy(p3) < 0. {
+            return None;
+        }
+        let segment = xy(p3 - p0);
+        if segment.sq() == 0. {
+            return None;
+        }
+        let w = (-(xy(p0) * segment) / segment.sq()).clamp(0., 1.);
+        let u = u0 + w * (u1 - u0);
+        let half = 0.5 * self.width_at(u);
+        let centre = eval(points, w);
+        let miss = xy(centre).sq();
+        if miss > half * half {
+            return None;
+        }
+        // the depth of the near side of the curve's tube, so rays leaving the
+        // curve, which start on or inside it, fall short of `zmin`
+        let z = centre.z - (half * half - miss).sqrt();
+        (zmin <= z && z <= zmax).then_some((z, u))
+    }
+}
+
+impl Curves {
+    pub fn new(curves: Vec<Curve>, kind: CurveKind, material: Material) -> Self {
+        let bounds = curves.iter().map(Curve::bounds).collect::<Vec<_>>();
+        Self {
+            bvh: Bvh::new(&bounds),
+            curves,
+            kind,
+            hair: false,
+            material,
+        }
+    }
+    pub fn curves(&self) -> &[Curve] {
+        &self.curves
+    }
+}
+
+impl Prop for Curves {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let len = ray.dir.abs();
+        let forward = ray.dir / len;
+        let [right, up] = forward.orthonormal();
+        let to_ray = |p: Vector| {
+            let d = p - ray.eye;
+            Vector::new(right * d, up * d, forward * d)
+        };
+
+        let (t, (curve, u)) = self.bvh.nearest(ray, eps, |i, limit| {
+            let curve = &self.curves[i];
+            let points = curve.points.map(to_ray);
+            let (z, u) =
+                curve.recurse(points, [0., 1.], curve.depth(), [eps * len, limit * len])?;
+            Some((z / len, (curve, u)))
+        })?;
+
+        let tangent = curve.tangent(u);
+        let position = ray.at(t);
+        let facing = -(forward - (forward * tangent) * tangent).norm();
+        let side = tangent ^ facing;
+        let half = 0.5 * curve.width_at(u);
+        let offset = ((position - curve.point(u)) * side / half).clamp(-1., 1.);
+        let normal = match self.kind {
+            CurveKind::Ribbon => facing,
+            CurveKind::Cylinder => (1. - offset * offset).sqrt() * facing + offset * side,
+        };
+
+        Some(HitRecord {
+            uv: Some([u, 0.5 * (offset + 1.)]),
+            shading: if self.hair {
+                Shading::Hair { tangent }
+            } else {
+                Shading::Phong
+            },
+            ..HitRecord::new(self, ray, t, normal, self.material)
+        })
+    }
+    fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+}
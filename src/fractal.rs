@@ -0,0 +1,161 @@
+use crate::pixel::Rgb;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::sdf::{Sdf, SdfProp};
+use crate::vector::{Aabb, Quaternion, Ray, Vector};
+
+pub trait Fractal: Sdf {
+    fn estimate(&self, p: Vector) -> (f64, f64);
+    fn trap(&self, p: Vector) -> f64 {
+        self.estimate(p).1
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: usize,
+    pub bailout: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MengerSponge {
+    pub iterations: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QuaternionJulia {
+    pub c: Quaternion,
+    pub slice: f64,
+    pub iterations: usize,
+    pub bailout: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FractalProp<F: Fractal> {
+    pub prop: SdfProp<F>,
+    pub palette: [Rgb; 2],
+}
+
+impl Fractal for Mandelbulb {
+    fn estimate(&self, p: Vector) -> (f64, f64) {
+        let mut z = p;
+        let mut dr = 1.;
+        let mut r = z.abs();
+        let mut trap = f64::INFINITY;
+        for _ in 0..self.iterations {
+            r = z.abs();
+            if r > self.bailout {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.) * self.power * dr + 1.;
+            let zr = r.powf(self.power);
+            let sin = theta.sin();
+            z = zr * Vector::new(sin * phi.cos(), sin * phi.sin(), theta.cos()) + p;
+            trap = trap.min(z.sq());
+        }
+        (0.5 * r.ln() * r / dr, trap.clamp(0., 1.))
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Vector) -> f64 {
+        self.estimate(p).0
+    }
+    fn bounds(&self) -> Aabb {
+        // orbits from beyond 2^(1/(power - 1)) grow without bound
+        let escape = if self.power > 1. {
+            2f64.powf(1. / (self.power - 1.))
+        } else {
+            self.bailout
+        };
+        Aabb::around(Vector::default(), 1.1 * escape.min(self.bailout))
+    }
+}
+
+impl Fractal for MengerSponge {
+    fn estimate(&self, p: Vector) -> (f64, f64) {
+        let q = Vector::new(p.x.abs(), p.y.abs(), p.z.abs()) - Vector::new(1., 1., 1.);
+        let mut d = Vector::new(q.x.max(0.), q.y.max(0.), q.z.max(0.)).abs()
+            + q.x.max(q.y).max(q.z).min(0.);
+        let mut trap = 0.;
+        let mut s = 1.;
+        for i in 0..self.iterations {
+            let a = (p * s).to_array().map(|c| c.rem_euclid(2.) - 1.);
+            s *= 3.;
+            let r = a.map(|c| (1. - 3. * c.abs()).abs());
+            let c = (r[0].max(r[1]).min(r[1].max(r[2])).min(r[2].max(r[0])) - 1.) / s;
+            if c > d {
+                d = c;
+                trap = (i + 1) as f64 / self.iterations as f64;
+            }
+        }
+        (d, trap)
+    }
+}
+
+impl Sdf for MengerSponge {
+    fn distance(&self, p: Vector) -> f64 {
+        self.estimate(p).0
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::around(Vector::default(), 1.)
+    }
+}
+
+impl Fractal for QuaternionJulia {
+    fn estimate(&self, p: Vector) -> (f64, f64) {
+        let mut z = Quaternion::new(p.x, p.y, p.z, self.slice);
+        let mut dz = Quaternion::new(1., 0., 0., 0.);
+        let mut trap = f64::INFINITY;
+        for _ in 0..self.iterations {
+            dz = (z * dz).scale(2.);
+            z = z * z + self.c;
+            trap = trap.min(z.sq());
+            if z.sq() > self.bailout * self.bailout {
+                break;
+            }
+        }
+        let r = z.abs();
+        (0.5 * r * r.ln() / dz.abs(), trap.clamp(0., 1.))
+    }
+}
+
+impl Sdf for QuaternionJulia {
+    fn distance(&self, p: Vector) -> f64 {
+        self.estimate(p).0
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::around(Vector::default(), self.bailout.min(2.))
+    }
+}
+
+impl<F: Fractal> FractalProp<F> {
+    pub const fn new(fractal: F, material: Material, palette: [Rgb; 2]) -> Self {
+        let mut prop = SdfProp::new(fractal, material);
+        prop.steps = 512;
+        Self { prop, palette }
+    }
+    fn colour<'a>(&self, mut hit: HitRecord<'a>) -> HitRecord<'a> {
+        let trap = self.prop.sdf.trap(hit.position);
+        hit.material.colour = self.palette[0].lerp(self.palette[1], trap);
+        hit
+    }
+}
+
+impl<F: Fractal> Prop for FractalProp<F> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        Some(self.colour(self.prop.raycast(ray, eps)?))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.prop
+            .raycast_all(ray, eps)
+            .into_iter()
+            .map(|hit| self.colour(hit))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.prop.bounds()
+    }
+}
@@ -5,6 +5,21 @@ use std::ops::{Index, IndexMut};
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Image<P: Pixel, const W: usize, const H: usize>(Box<[[P; W]; H]>);
 
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Downsample {
+    #[default]
+    Box,
+    Kaiser,
+}
+
+// A level of a mip pyramid, whose size isn't known at compile time.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mip<P: Pixel> {
+    width: usize,
+    height: usize,
+    pixels: Vec<P>,
+}
+
 impl<P: Pixel, const W: usize, const H: usize> Index<[usize; 2]> for Image<P, W, H> {
     type Output = P;
     fn index(&self, index: [usize; 2]) -> &Self::Output {
@@ -196,4 +211,142 @@ impl<P: Pixel, const W: usize, const H: usize> Image<P, W, H> {
     pub fn iter_mut(&mut self) -> <&mut Self as IntoIterator>::IntoIter {
         self.into_iter()
     }
+    // The levels below this one, each half the size of the last, down to 1x1.
+    pub fn mipmap(&self, downsample: Downsample) -> Vec<Mip<P>> {
+        let mut levels: Vec<Mip<P>> = Vec::new();
+        let mut dims = [W, H];
+        while dims != [1, 1] {
+            let level = match levels.last() {
+                Some(last) => reduce(dims, |p| last[p], downsample),
+                None => reduce(dims, |p| self[p], downsample),
+            };
+            dims = level.dims();
+            levels.push(level);
+        }
+        levels
+    }
+}
+
+impl<P: Pixel> Index<[usize; 2]> for Mip<P> {
+    type Output = P;
+    fn index(&self, index: [usize; 2]) -> &Self::Output {
+        &self.pixels[index[1] * self.width + index[0]]
+    }
+}
+
+impl<P: Pixel> Mip<P> {
+    pub const fn dims(&self) -> [usize; 2] {
+        [self.width, self.height]
+    }
+    pub fn downsample(&self, downsample: Downsample) -> Self {
+        reduce(self.dims(), |p| self[p], downsample)
+    }
+}
+
+// Modified Bessel function of the first kind, order zero.
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term) = (1., 1.);
+    for k in 1..32 {
+        term *= x * x / (4. * (k * k) as f64);
+        sum += term;
+    }
+    sum
+}
+
+// Normalised filter taps for output sample `i` of a row of `n` halved.
+fn taps(downsample: Downsample, i: usize, n: usize) -> Vec<(usize, f64)> {
+    const ALPHA: f64 = 4.;
+    const RADIUS: isize = 4;
+    let centre = 2. * i as f64 + 0.5;
+    let mut taps = match downsample {
+        // the last sample of an odd row takes the pixel left over
+        Downsample::Box if n % 2 == 1 && i == n / 2 - 1 => (2 * i as isize..2 * i as isize + 3)
+            .map(|j| (j, 1.))
+            .collect(),
+        Downsample::Box => vec![(2 * i as isize, 1.), (2 * i as isize + 1, 1.)],
+        // a Kaiser-windowed sinc, four source pixels either side
+        Downsample::Kaiser => (2 * i as isize - RADIUS + 1..=2 * i as isize + RADIUS)
+            .map(|j| {
+                let x = (j as f64 - centre) / 2.;
+                let sinc = if x == 0. {
+                    1.
+                } else {
+                    (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x)
+                };
+                let r = x / (RADIUS as f64 / 2.);
+                let window = bessel_i0(ALPHA * (1. - r * r).max(0.).sqrt()) / bessel_i0(ALPHA);
+                (j, sinc * window)
+            })
+            .collect(),
+    }
+    .into_iter()
+    .map(|(j, w)| (j.clamp(0, n as isize - 1) as usize, w))
+    .collect::<Vec<_>>();
+    let total = taps.iter().map(|&(_, w)| w).sum::<f64>();
+    for (_, w) in &mut taps {
+        *w /= total;
+    }
+    taps
+}
+
+// Halves each dimension with a separable filter, working in floating point
+// so rounding happens once.
+fn reduce<P: Pixel>(
+    [w, h]: [usize; 2],
+    get: impl Fn([usize; 2]) -> P,
+    downsample: Downsample,
+) -> Mip<P> {
+    let (width, height) = ((w / 2).max(1), (h / 2).max(1));
+    let channels = |p: P| {
+        let Rgba { r, g, b, a } = p.to_rgba();
+        [r, g, b, a].map(f64::from)
+    };
+    let filter = |taps: &[(usize, f64)], at: &dyn Fn(usize) -> [f64; 4]| {
+        taps.iter().fold([0.; 4], |mut acc, &(j, weight)| {
+            for (acc, c) in acc.iter_mut().zip(at(j)) {
+                *acc += weight * c;
+            }
+            acc
+        })
+    };
+
+    let across = (0..width)
+        .map(|i| {
+            if w == 1 {
+                vec![(0, 1.)]
+            } else {
+                taps(downsample, i, w)
+            }
+        })
+        .collect::<Vec<_>>();
+    let down = (0..height)
+        .map(|j| {
+            if h == 1 {
+                vec![(0, 1.)]
+            } else {
+                taps(downsample, j, h)
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut rows = Vec::with_capacity(width * h);
+    for y in 0..h {
+        rows.extend(
+            across
+                .iter()
+                .map(|taps| filter(taps, &|x| channels(get([x, y])))),
+        );
+    }
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let [r, g, b, a] = filter(&down[y], &|row| rows[row * width + x])
+                .map(|c| c.round().clamp(0., 255.) as u8);
+            P::from_rgba(Rgba { r, g, b, a })
+        })
+        .collect();
+    Mip {
+        width,
+        height,
+        pixels,
+    }
 }
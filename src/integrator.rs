@@ -0,0 +1,285 @@
+use crate::pbr::{self, Pbr};
+use crate::pixel::Colour;
+use crate::prop::{HitRecord, Shading};
+use crate::rng::{self, Sampler, Uniform};
+use crate::scene::{Projection, Scene};
+use crate::vector::{Ray, Vector};
+use std::f64::consts::{PI, TAU};
+
+pub trait Integrator: std::fmt::Debug {
+    // The light arriving back along a camera ray, or `None` if the ray leaves
+    // the scene so the background shows through.
+    fn radiance(&self, scene: &Scene, ray: Ray, sampler: &mut Sampler) -> Option<Colour>;
+    // Integrators whose samples land on other pixels than the one being
+    // traced render the whole frame at once, row by row, with `None` where
+    // the background shows through; the rest leave it to `radiance`.
+    fn render(
+        &self,
+        _scene: &Scene,
+        _projection: &dyn Projection,
+        _dims: [usize; 2],
+    ) -> Option<Vec<Option<Colour>>> {
+        None
+    }
+}
+
+// `Scene::shade`: local Phong, hair or PBR shading with a hard shadow.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Local;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathTracer {
+    pub max_depth: usize,
+    // the bounce from which paths may be terminated early
+    pub roulette_depth: usize,
+    // radiance arriving from every direction that leaves the scene
+    pub sky: Colour,
+}
+
+// What the physically based integrators scatter with; Phong materials become
+// a Lambertian lobe plus a normalised Phong lobe, and hair is treated as
+// diffuse. `n` faces `wo` throughout, and both directions are unit vectors
+// pointing away from the surface.
+#[derive(Clone, Copy, Debug)]
+pub enum Bsdf {
+    Phong {
+        diffuse: Colour,
+        specular: f64,
+        exponent: f64,
+    },
+    Pbr {
+        base: Colour,
+        pbr: Pbr,
+    },
+    Dielectric {
+        tint: Colour,
+        // the index beyond the surface over the index on `wo`'s side
+        eta: f64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scatter {
+    pub dir: Vector,
+    pub weight: Colour,
+    // infinite for specular scattering
+    pub pdf: f64,
+}
+
+fn reflect(w: Vector, n: Vector) -> Vector {
+    2. * (w * n) * n - w
+}
+
+// The ray leaving `hit` along `dir`; mirror reflections keep the incoming
+// ray's differentials so textures seen in them stay filtered.
+pub fn next_ray(hit: &HitRecord, bsdf: &Bsdf, dir: Vector) -> Ray {
+    let n = hit.shading_normal();
+    if bsdf.is_delta() && (n * dir) * (n * hit.ray.dir) < 0. {
+        hit.reflected()
+    } else {
+        Ray::new(hit.position, dir).with_time(hit.ray.time)
+    }
+}
+
+// Veach's power heuristic for two strategies taking one sample each.
+fn power(pdf: f64, other: f64) -> f64 {
+    if pdf.is_infinite() {
+        1.
+    } else {
+        pdf * pdf / (pdf * pdf + other * other)
+    }
+}
+
+impl Bsdf {
+    pub fn new(hit: &HitRecord) -> Self {
+        let colour = Colour::from(hit.material.colour);
+        match hit.shading {
+            Shading::Pbr(pbr) => Self::Pbr { base: colour, pbr },
+            Shading::Dielectric { ior } => Self::Dielectric {
+                tint: colour,
+                eta: if hit.entering() { ior } else { 1. / ior },
+            },
+            Shading::Phong | Shading::Hair { .. } => Self::Phong {
+                diffuse: hit.material.diffuse * colour,
+                specular: hit.material.specular,
+                exponent: hit.material.shininess,
+            },
+        }
+    }
+    // The chance of sampling the specular lobe rather than the diffuse one.
+    fn specular_weight(&self, n: Vector, wo: Vector) -> f64 {
+        let (specular, diffuse) = match *self {
+            Self::Phong {
+                diffuse, specular, ..
+            } => (specular, diffuse.luminance()),
+            Self::Pbr { base, pbr } => (
+                pbr::schlick(pbr.f0(base), n * wo).luminance(),
+                ((1. - pbr.metallic) * base).luminance(),
+            ),
+            Self::Dielectric { .. } => return 0.,
+        };
+        if specular + diffuse > 0. {
+            specular / (specular + diffuse)
+        } else {
+            0.
+        }
+    }
+    pub fn eval(&self, n: Vector, wo: Vector, wi: Vector) -> Colour {
+        match *self {
+            Self::Phong {
+                diffuse,
+                specular,
+                exponent,
+            } => {
+                if n * wi <= 0. || n * wo <= 0. {
+                    return Colour::BLACK;
+                }
+                let lobe = (exponent + 2.) / TAU * (reflect(wo, n) * wi).max(0.).powf(exponent);
+                diffuse / PI + specular * lobe * Colour::WHITE
+            }
+            Self::Pbr { base, pbr } => pbr.eval(base, n, wo, wi),
+            Self::Dielectric { .. } => Colour::BLACK,
+        }
+    }
+    pub fn pdf(&self, n: Vector, wo: Vector, wi: Vector) -> f64 {
+        let cos = n * wi;
+        if cos <= 0. {
+            return 0.;
+        }
+        let specular = match *self {
+            Self::Phong { exponent, .. } => rng::power_cosine_pdf(exponent, reflect(wo, n) * wi),
+            Self::Pbr { pbr, .. } => {
+                let h = (wo + wi).norm();
+                pbr::ggx(n * h, pbr.alpha()) * (n * h) / (4. * (wo * h).abs())
+            }
+            Self::Dielectric { .. } => return 0.,
+        };
+        let weight = self.specular_weight(n, wo);
+        (1. - weight) * rng::cosine_hemisphere_pdf(cos) + weight * specular
+    }
+    pub fn is_delta(&self) -> bool {
+        matches!(self, Self::Dielectric { .. })
+    }
+    pub fn sample(&self, n: Vector, wo: Vector, sampler: &mut impl Uniform) -> Option<Vector> {
+        let weight = self.specular_weight(n, wo);
+        let u = sampler.uniform_2d();
+        let wi = match *self {
+            Self::Dielectric { eta, .. } => {
+                if u[0] < pbr::fresnel(n * wo, eta) {
+                    return Some(reflect(wo, n));
+                }
+                return pbr::refract(wo, n, eta);
+            }
+            _ if sampler.uniform() >= weight => rng::frame(n, rng::cosine_hemisphere(u)),
+            Self::Phong { exponent, .. } => {
+                rng::frame(reflect(wo, n), rng::power_cosine(exponent, u))
+            }
+            // half vectors distributed as D(h) cos θh
+            Self::Pbr { pbr, .. } => {
+                let [u1, u2] = u;
+                let a2 = pbr.alpha() * pbr.alpha();
+                let cos = ((1. - u1) / (1. + (a2 - 1.) * u1)).sqrt();
+                reflect(wo, rng::frame(n, rng::spherical(cos, TAU * u2)))
+            }
+        };
+        (n * wi > 0.).then_some(wi)
+    }
+    pub fn scatter(&self, n: Vector, wo: Vector, sampler: &mut impl Uniform) -> Option<Scatter> {
+        let dir = self.sample(n, wo, sampler)?;
+        if let Self::Dielectric { tint, .. } = *self {
+            let weight = if n * dir > 0. { Colour::WHITE } else { tint };
+            return Some(Scatter {
+                dir,
+                weight,
+                pdf: f64::INFINITY,
+            });
+        }
+        let pdf = self.pdf(n, wo, dir);
+        (pdf > 0.).then(|| Scatter {
+            dir,
+            weight: (n * dir / pdf) * self.eval(n, wo, dir),
+            pdf,
+        })
+    }
+}
+
+impl Integrator for Local {
+    fn radiance(&self, scene: &Scene, ray: Ray, _: &mut Sampler) -> Option<Colour> {
+        scene.trace(ray).map(Colour::from)
+    }
+}
+
+impl PathTracer {
+    pub const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            roulette_depth: 3,
+            sky: Colour::BLACK,
+        }
+    }
+}
+
+impl Default for PathTracer {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl Integrator for PathTracer {
+    fn radiance(&self, scene: &Scene, mut ray: Ray, sampler: &mut Sampler) -> Option<Colour> {
+        let light = &scene.light;
+        let mut radiance = Colour::BLACK;
+        let mut throughput = Colour::WHITE;
+        // the pdf of the BSDF sample that chose `ray`, for weighting light hits
+        let mut scattered: Option<f64> = None;
+
+        for depth in 0..self.max_depth {
+            let hit = scene.hit(ray);
+            if let Some(t) = light.hit(ray, scene.eps)
+                && hit.as_ref().is_none_or(|hit| hit.t() > t)
+            {
+                let weight = scattered.map_or(1., |pdf| power(pdf, light.pdf(ray.eye)));
+                return Some(radiance + weight * throughput * light.radiance());
+            }
+            let Some(hit) = hit else {
+                return (depth > 0).then_some(radiance + throughput * self.sky);
+            };
+
+            let wo = -ray.dir.norm();
+            let n = hit.shading_normal();
+            let n = if hit.normal * wo < 0. { -n } else { n };
+            let p = hit.position;
+            if let Shading::Pbr(pbr) = hit.shading {
+                radiance += throughput * pbr.emissive;
+            }
+            let bsdf = Bsdf::new(&hit);
+
+            // next-event estimation, weighted against the BSDF finding the light
+            if !bsdf.is_delta()
+                && let Some(sample) = light.sample(p, sampler.uniform_2d())
+                && n * sample.dir > 0.
+                && !scene.occluded(p, p + sample.distance * sample.dir, ray.time)
+            {
+                let weight = power(sample.pdf, bsdf.pdf(n, wo, sample.dir));
+                let f = bsdf.eval(n, wo, sample.dir);
+                radiance += (weight * (n * sample.dir)) * throughput * f * sample.value;
+            }
+
+            let Some(scatter) = bsdf.scatter(n, wo, sampler) else {
+                break;
+            };
+            throughput *= scatter.weight;
+
+            if depth + 1 >= self.roulette_depth {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(0.95);
+                if sampler.uniform() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+            ray = next_ray(&hit, &bsdf, scatter.dir);
+            scattered = Some(scatter.pdf);
+        }
+        Some(radiance)
+    }
+}
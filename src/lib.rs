@@ -1,5 +1,28 @@
+pub mod bdpt;
+pub mod bvh;
+pub mod csg;
+pub mod curve;
+pub mod fractal;
 pub mod image;
+pub mod integrator;
+pub mod matrix;
+pub mod metaball;
+pub mod mlt;
+pub mod noise;
+pub mod patch;
+pub mod pbr;
+pub mod photon;
 pub mod pixel;
+pub mod pointcloud;
+pub mod poly;
+pub mod primitive;
 pub mod prop;
+pub mod quadric;
+pub mod rng;
 pub mod scene;
+pub mod sdf;
+pub mod stereo;
+pub mod terrain;
+pub mod texture;
 pub mod vector;
+pub mod voxel;
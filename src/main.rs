@@ -30,12 +30,10 @@ fn main() -> Result<()> {
                 },
             }),
         ],
-        light: Light {
-            position: Vector::new(-5., 13., -15.),
-            colour: Rgb::white(),
-        },
-        camera: Camera::pz_towards_origin(20., 120.),
-        eps: 1e-6,
+        ..Scene::new(
+            Light::point(Vector::new(-5., 13., -15.), Rgb::white()),
+            Camera::pz_towards_origin(20., 120.),
+        )
     };
 
     stdout().write_all(
@@ -0,0 +1,344 @@
+use crate::vector::{Aabb, Differentials, Quaternion, Ray, Vector};
+use std::ops::{Index, IndexMut, Mul, MulAssign};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4(pub [[f64; 4]; 4]);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+}
+
+impl Matrix4 {
+    pub const IDENTITY: Self = Self([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]);
+
+    pub const fn new(rows: [[f64; 4]; 4]) -> Self {
+        Self(rows)
+    }
+    pub const fn from_basis(x: Vector, y: Vector, z: Vector, origin: Vector) -> Self {
+        Self([
+            [x.x, y.x, z.x, origin.x],
+            [x.y, y.y, z.y, origin.y],
+            [x.z, y.z, z.z, origin.z],
+            [0., 0., 0., 1.],
+        ])
+    }
+    pub const fn translate(offset: Vector) -> Self {
+        Self([
+            [1., 0., 0., offset.x],
+            [0., 1., 0., offset.y],
+            [0., 0., 1., offset.z],
+            [0., 0., 0., 1.],
+        ])
+    }
+    pub const fn scale(factor: Vector) -> Self {
+        Self([
+            [factor.x, 0., 0., 0.],
+            [0., factor.y, 0., 0.],
+            [0., 0., factor.z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+    pub const fn shear(axis: usize, onto: usize, factor: f64) -> Self {
+        assert!(
+            axis < 3 && onto < 3 && axis != onto,
+            "shear needs two distinct axes"
+        );
+        let mut m = Self::IDENTITY;
+        m.0[onto][axis] += factor;
+        m
+    }
+    pub fn rotate_on_axis(axis: usize, theta: f64) -> Self {
+        let (sin, cos) = theta.to_radians().sin_cos();
+        let a = (axis + 1) % 3;
+        let b = (axis + 2) % 3;
+        let mut m = Self::IDENTITY;
+        m.0[a][a] = cos;
+        m.0[a][b] = -sin;
+        m.0[b][a] = sin;
+        m.0[b][b] = cos;
+        m
+    }
+    pub fn rotate(axis: Vector, theta: f64) -> Self {
+        let Vector { x, y, z } = axis.norm();
+        let (sin, cos) = theta.to_radians().sin_cos();
+        let c = 1. - cos;
+        let (xy, xz, yz) = (x * y * c, x * z * c, y * z * c);
+        let (xs, ys, zs) = (x * sin, y * sin, z * sin);
+        Self([
+            [cos + x * x * c, xy - zs, xz + ys, 0.],
+            [xy + zs, cos + y * y * c, yz - xs, 0.],
+            [xz - ys, yz + xs, cos + z * z * c, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+    pub const fn transpose(self) -> Self {
+        let mut m = self;
+        let mut i = 0;
+        while i < 4 {
+            let mut j = 0;
+            while j < 4 {
+                m.0[i][j] = self.0[j][i];
+                j += 1;
+            }
+            i += 1;
+        }
+        m
+    }
+    pub const fn mul(self, rhs: Self) -> Self {
+        let mut m = Self([[0.; 4]; 4]);
+        let mut i = 0;
+        while i < 4 {
+            let mut j = 0;
+            while j < 4 {
+                let mut k = 0;
+                while k < 4 {
+                    m.0[i][j] += self.0[i][k] * rhs.0[k][j];
+                    k += 1;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        m
+    }
+    pub fn determinant(&self) -> f64 {
+        let mut lu = self.0;
+        let mut det = 1.;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| lu[i][col].abs().total_cmp(&lu[j][col].abs()))
+                .unwrap();
+            if lu[pivot][col] == 0. {
+                return 0.;
+            }
+            if pivot != col {
+                lu.swap(pivot, col);
+                det = -det;
+            }
+            det *= lu[col][col];
+            let pivot_row = lu[col];
+            for row in &mut lu[col + 1..] {
+                let f = row[col] / pivot_row[col];
+                for (v, p) in row.iter_mut().zip(pivot_row) {
+                    *v -= f * p;
+                }
+            }
+        }
+        det
+    }
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.0;
+        let mut inv = Self::IDENTITY.0;
+        // pivots are judged against the size of their column, which row
+        // operations scale along with them, so scaling a column never changes
+        // whether the matrix inverts
+        let size = |col: usize| (0..4).fold(0f64, |size, row| size.max(self.0[row][col].abs()));
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() <= 1e-12 * size(col) {
+                return None;
+            }
+            a.swap(pivot, col);
+            inv.swap(pivot, col);
+
+            let p = a[col][col];
+            a[col] = a[col].map(|v| v / p);
+            inv[col] = inv[col].map(|v| v / p);
+            let (a_row, inv_row) = (a[col], inv[col]);
+            for row in (0..4).filter(|&row| row != col) {
+                let f = a[row][col];
+                for k in 0..4 {
+                    a[row][k] -= f * a_row[k];
+                    inv[row][k] -= f * inv_row[k];
+                }
+            }
+        }
+        Some(Self(inv))
+    }
+    pub const fn point(&self, p: Vector) -> Vector {
+        let m = &self.0;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1. {
+            Vector::new(x, y, z)
+        } else {
+            Vector::new(x / w, y / w, z / w)
+        }
+    }
+    pub const fn vector(&self, v: Vector) -> Vector {
+        let m = &self.0;
+        Vector::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Index<[usize; 2]> for Matrix4 {
+    type Output = f64;
+    fn index(&self, [row, col]: [usize; 2]) -> &Self::Output {
+        &self.0[row][col]
+    }
+}
+
+impl IndexMut<[usize; 2]> for Matrix4 {
+    fn index_mut(&mut self, [row, col]: [usize; 2]) -> &mut Self::Output {
+        &mut self.0[row][col]
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.mul(rhs)
+    }
+}
+
+impl MulAssign for Matrix4 {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = self.mul(rhs)
+    }
+}
+
+impl Mul<Vector> for Matrix4 {
+    type Output = Vector;
+    fn mul(self, rhs: Vector) -> Self::Output {
+        self.point(rhs)
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        matrix: Matrix4::IDENTITY,
+        inverse: Matrix4::IDENTITY,
+    };
+
+    pub fn new(matrix: Matrix4) -> Option<Self> {
+        matrix.inverse().map(|inverse| Self { matrix, inverse })
+    }
+    pub const fn translate(offset: Vector) -> Self {
+        Self {
+            matrix: Matrix4::translate(offset),
+            inverse: Matrix4::translate(offset.neg()),
+        }
+    }
+    pub const fn scale(factor: Vector) -> Self {
+        assert!(
+            factor.x != 0. && factor.y != 0. && factor.z != 0.,
+            "scale factors must be non-zero"
+        );
+        Self {
+            matrix: Matrix4::scale(factor),
+            inverse: Matrix4::scale(Vector::new(1. / factor.x, 1. / factor.y, 1. / factor.z)),
+        }
+    }
+    pub const fn shear(axis: usize, onto: usize, factor: f64) -> Self {
+        Self {
+            matrix: Matrix4::shear(axis, onto, factor),
+            inverse: Matrix4::shear(axis, onto, -factor),
+        }
+    }
+    pub fn rotate_on_axis(axis: usize, theta: f64) -> Self {
+        let matrix = Matrix4::rotate_on_axis(axis, theta);
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+    pub fn rotate(axis: Vector, theta: f64) -> Self {
+        let matrix = Matrix4::rotate(axis, theta);
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+    pub const fn rotation(q: Quaternion) -> Self {
+        let matrix = q.to_matrix();
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+    pub const fn inverse(self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+    pub const fn then(self, next: Self) -> Self {
+        Self {
+            matrix: next.matrix.mul(self.matrix),
+            inverse: self.inverse.mul(next.inverse),
+        }
+    }
+    pub const fn point(&self, p: Vector) -> Vector {
+        self.matrix.point(p)
+    }
+    pub const fn vector(&self, v: Vector) -> Vector {
+        self.matrix.vector(v)
+    }
+    pub const fn normal(&self, n: Vector) -> Vector {
+        self.inverse.transpose().vector(n)
+    }
+    pub const fn ray(&self, ray: Ray) -> Ray {
+        Ray {
+            eye: self.point(ray.eye),
+            dir: self.vector(ray.dir),
+            differentials: match ray.differentials {
+                Some(Differentials { eye, dir }) => Some(Differentials {
+                    eye: [self.vector(eye[0]), self.vector(eye[1])],
+                    dir: [self.vector(dir[0]), self.vector(dir[1])],
+                }),
+                None => None,
+            },
+            ..ray
+        }
+    }
+    pub fn aabb(&self, aabb: Aabb) -> Aabb {
+        if aabb == Aabb::EMPTY {
+            return aabb;
+        }
+        if !aabb.extent().abs().is_finite() {
+            return Aabb::INFINITE;
+        }
+        (0..8).fold(Aabb::EMPTY, |acc, i| {
+            let corner = Vector::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            );
+            acc.include(self.point(corner))
+        })
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        rhs.then(self)
+    }
+}
@@ -0,0 +1,155 @@
+use crate::poly;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ball {
+    pub centre: Vector,
+    pub radius: f64,
+    pub weight: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Metaballs {
+    pub balls: Vec<Ball>,
+    pub threshold: f64,
+    pub material: Material,
+    pub steps: usize,
+}
+
+impl Ball {
+    // Wyvill's compactly supported kernel, zero with zero slope at the radius.
+    pub fn field(&self, p: Vector) -> f64 {
+        let s = 1. - (p - self.centre).sq() / (self.radius * self.radius);
+        if s > 0. { self.weight * s * s * s } else { 0. }
+    }
+    pub fn gradient(&self, p: Vector) -> Vector {
+        let r2 = self.radius * self.radius;
+        let d = p - self.centre;
+        let s = 1. - d.sq() / r2;
+        if s > 0. {
+            -6. * self.weight * s * s / r2 * d
+        } else {
+            Vector::default()
+        }
+    }
+    fn span(&self, ray: Ray) -> Option<[f64; 2]> {
+        poly::sphere(ray.eye - self.centre, ray.dir, self.radius)
+    }
+}
+
+impl Metaballs {
+    pub const fn new(balls: Vec<Ball>, threshold: f64, material: Material) -> Self {
+        Self {
+            balls,
+            threshold,
+            material,
+            steps: 16,
+        }
+    }
+    pub fn field(&self, p: Vector) -> f64 {
+        self.balls.iter().map(|ball| ball.field(p)).sum()
+    }
+    pub fn gradient(&self, p: Vector) -> Vector {
+        self.balls
+            .iter()
+            .fold(Vector::default(), |acc, ball| acc + ball.gradient(p))
+    }
+    fn roots(&self, ray: Ray, eps: f64, first: bool) -> Vec<f64> {
+        // sweep the spans where each ball has influence, so only the balls
+        // active on a span are summed while bracketing
+        let mut events = self
+            .balls
+            .iter()
+            .enumerate()
+            .filter_map(|(i, ball)| Some((i, ball.span(ray)?)))
+            .filter(|&(_, [_, exit])| exit >= eps)
+            .flat_map(|(i, [entry, exit])| [(entry.max(eps), i, true), (exit, i, false)])
+            .collect::<Vec<_>>();
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut active = Vec::new();
+        let mut roots = Vec::new();
+        for pair in events.windows(2) {
+            let (start, ball, entering) = pair[0];
+            if entering {
+                active.push(ball);
+            } else {
+                active.retain(|&i| i != ball);
+            }
+            let end = pair[1].0;
+            if active.is_empty() || end <= start {
+                continue;
+            }
+
+            let g = |t: f64| {
+                let p = ray.at(t);
+                active.iter().map(|&i| self.balls[i].field(p)).sum::<f64>() - self.threshold
+            };
+            let h = (end - start) / self.steps as f64;
+            let (mut a, mut ga) = (start, g(start));
+            for k in 1..=self.steps {
+                let b = if k == self.steps {
+                    end
+                } else {
+                    start + k as f64 * h
+                };
+                let gb = g(b);
+                if ga * gb < 0. || (gb == 0. && ga != 0.) {
+                    roots.push(Self::refine(&g, [a, b], [ga, gb]));
+                    if first {
+                        return roots;
+                    }
+                }
+                (a, ga) = (b, gb);
+            }
+        }
+        roots
+    }
+    // Illinois variant of regula falsi on a bracketed sign change.
+    fn refine(
+        g: &impl Fn(f64) -> f64,
+        [mut a, mut b]: [f64; 2],
+        [mut ga, mut gb]: [f64; 2],
+    ) -> f64 {
+        for _ in 0..64 {
+            if gb == 0. || (b - a).abs() < 1e-12 * (1. + b.abs()) {
+                break;
+            }
+            let c = (a * gb - b * ga) / (gb - ga);
+            let gc = g(c);
+            if gc * gb < 0. {
+                (a, ga) = (b, gb);
+            } else {
+                ga /= 2.;
+            }
+            (b, gb) = (c, gc);
+        }
+        b
+    }
+    fn hit(&self, ray: Ray, t: f64) -> HitRecord<'_> {
+        let normal = -self.gradient(ray.at(t)).norm();
+        HitRecord::new(self, ray, t, normal, self.material)
+    }
+}
+
+impl Prop for Metaballs {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let t = *self.roots(ray, eps, true).first()?;
+        Some(self.hit(ray, t))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.roots(ray, eps, false)
+            .into_iter()
+            .map(|t| self.hit(ray, t))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.balls
+            .iter()
+            .filter(|ball| ball.weight > 0.)
+            .fold(Aabb::EMPTY, |acc, ball| {
+                acc.union(Aabb::around(ball.centre, ball.radius))
+            })
+    }
+}
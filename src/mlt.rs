@@ -0,0 +1,271 @@
+use crate::bdpt::{self, Bdpt, View};
+use crate::integrator::Integrator;
+use crate::pixel::Colour;
+use crate::rng::{Pcg32, Rng, Sampler, Uniform};
+use crate::scene::{Projection, Scene};
+use crate::vector::Ray;
+use std::f64::consts::TAU;
+
+// Kelemen et al.'s primary sample space Metropolis light transport over the
+// bidirectional strategies. `Scene::samples` counts mutations per pixel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mlt {
+    pub max_depth: usize,
+    // paths per depth for estimating the image's total brightness
+    pub bootstrap: usize,
+    pub chains: usize,
+    pub large_step: f64,
+    pub sigma: f64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct PrimarySample {
+    value: f64,
+    // the iteration that last changed it, as changes are applied lazily
+    modified: u64,
+    backup: f64,
+    backup_modified: u64,
+}
+
+// The chain's state, in interleaved streams for the eye's path, the light's
+// path and the join, so each keeps its numbers as the others change length.
+#[derive(Clone, Debug)]
+struct MltSampler {
+    rng: Pcg32,
+    sigma: f64,
+    large_step: f64,
+    samples: Vec<PrimarySample>,
+    iteration: u64,
+    is_large: bool,
+    last_large: u64,
+    stream: usize,
+    index: usize,
+}
+
+const CAMERA: usize = 0;
+const LIGHT: usize = 1;
+const JOIN: usize = 2;
+const STREAMS: usize = 3;
+
+impl MltSampler {
+    fn new(mlt: &Mlt, seed: u64, index: u64) -> Self {
+        Self {
+            rng: Pcg32::new(seed, index),
+            sigma: mlt.sigma,
+            large_step: mlt.large_step,
+            samples: Vec::new(),
+            iteration: 0,
+            is_large: true,
+            last_large: 0,
+            stream: 0,
+            index: 0,
+        }
+    }
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.is_large = self.rng.uniform() < self.large_step;
+    }
+    fn start_stream(&mut self, stream: usize) {
+        self.stream = stream;
+        self.index = 0;
+    }
+    fn accept(&mut self) {
+        if self.is_large {
+            self.last_large = self.iteration;
+        }
+    }
+    fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.modified == self.iteration {
+                sample.value = sample.backup;
+                sample.modified = sample.backup_modified;
+            }
+        }
+        self.iteration -= 1;
+    }
+    fn mutate(&mut self, i: usize) {
+        if i >= self.samples.len() {
+            self.samples.resize(i + 1, PrimarySample::default());
+        }
+        let (rng, sample) = (&mut self.rng, &mut self.samples[i]);
+        if sample.modified < self.last_large {
+            sample.value = rng.uniform();
+            sample.modified = self.last_large;
+        }
+        sample.backup = sample.value;
+        sample.backup_modified = sample.modified;
+        if self.is_large {
+            sample.value = rng.uniform();
+        } else {
+            // the small steps missed compound into one of wider spread
+            let steps = (self.iteration - sample.modified) as f64;
+            let [u1, u2] = rng.uniform_2d();
+            let normal = (-2. * (1. - u1).ln()).sqrt() * (TAU * u2).cos();
+            sample.value += normal * self.sigma * steps.sqrt();
+            sample.value -= sample.value.floor();
+        }
+        sample.modified = self.iteration;
+    }
+}
+
+impl Uniform for MltSampler {
+    fn uniform(&mut self) -> f64 {
+        let i = self.stream + STREAMS * self.index;
+        self.index += 1;
+        self.mutate(i);
+        self.samples[i].value
+    }
+}
+
+impl Mlt {
+    pub const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            bootstrap: 10_000,
+            chains: 1000,
+            large_step: 0.3,
+            sigma: 0.01,
+        }
+    }
+    fn path(
+        &self,
+        scene: &Scene,
+        projection: &dyn Projection,
+        dims @ [w, h]: [usize; 2],
+        sampler: &mut MltSampler,
+        depth: usize,
+    ) -> Option<(Colour, [f64; 2])> {
+        sampler.start_stream(CAMERA);
+        // seeing the light directly only has the one strategy
+        let (strategies, s, t) = if depth == 0 {
+            (1, 0, 2)
+        } else {
+            let strategies = depth + 2;
+            let s = ((sampler.uniform() * strategies as f64) as usize).min(strategies - 1);
+            (strategies, s, strategies - s)
+        };
+        let [u, v] = sampler.uniform_2d();
+        let pixel = [u * w as f64 - 0.5, v * h as f64 - 0.5];
+        let [open, close] = projection.shutter();
+        let view = View {
+            scene,
+            projection,
+            dims,
+            time: open + (close - open) * sampler.uniform(),
+        };
+
+        let camera = bdpt::camera_path(&view, pixel, sampler, t);
+        if camera.len() != t {
+            return None;
+        }
+        sampler.start_stream(LIGHT);
+        let light = bdpt::light_path(&view, sampler, s);
+        if light.len() != s {
+            return None;
+        }
+        sampler.start_stream(JOIN);
+        let (value, splat) = bdpt::connect(&view, &light, &camera, sampler)?;
+        Some((value * strategies as f64, splat.unwrap_or(pixel)))
+    }
+}
+
+impl Default for Mlt {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl Integrator for Mlt {
+    fn radiance(&self, scene: &Scene, ray: Ray, sampler: &mut Sampler) -> Option<Colour> {
+        Bdpt::new(self.max_depth).radiance(scene, ray, sampler)
+    }
+    fn render(
+        &self,
+        scene: &Scene,
+        projection: &dyn Projection,
+        dims @ [w, h]: [usize; 2],
+    ) -> Option<Vec<Option<Colour>>> {
+        let brightness = |path: Option<(Colour, [f64; 2])>| path.map_or(0., |(c, _)| c.luminance());
+
+        // chains start by replaying the state behind a bootstrap path
+        let depths = self.max_depth + 1;
+        let bootstrap = (self.bootstrap * depths).max(1);
+        let mut cumulative = Vec::with_capacity(bootstrap);
+        let mut total = 0.;
+        for i in 0..bootstrap {
+            let mut sampler = MltSampler::new(self, scene.seed, i as u64);
+            total += brightness(self.path(scene, projection, dims, &mut sampler, i % depths));
+            cumulative.push(total);
+        }
+
+        let mut film = vec![Colour::BLACK; w * h];
+        let mut seen = vec![false; w * h];
+        let mut splat = |[x, y]: [f64; 2], value: Colour| {
+            let [x, y] = [(x + 0.5) as usize, (y + 0.5) as usize];
+            let i = y.min(h - 1) * w + x.min(w - 1);
+            film[i] += value;
+            seen[i] = true;
+        };
+        let mutations = scene.samples.max(1) * w * h;
+        let chains = self.chains.clamp(1, mutations);
+        let mut rng = Pcg32::new(scene.seed, bootstrap as u64);
+        for chain in 0..chains {
+            if total <= 0. {
+                break;
+            }
+            let steps = mutations / chains + usize::from(chain < mutations % chains);
+            let target = rng.uniform() * total;
+            let index = cumulative
+                .partition_point(|&c| c <= target)
+                .min(bootstrap - 1);
+            let mut sampler = MltSampler::new(self, scene.seed, index as u64);
+            let depth = index % depths;
+            let Some(mut current) = self.path(scene, projection, dims, &mut sampler, depth) else {
+                continue;
+            };
+
+            for _ in 0..steps {
+                sampler.start_iteration();
+                let proposed = self.path(scene, projection, dims, &mut sampler, depth);
+                let (y, y_current) = (brightness(proposed), current.0.luminance());
+                let accept = (y / y_current).min(1.);
+                if let Some((value, pixel)) = proposed
+                    && y > 0.
+                {
+                    splat(pixel, value * (accept / y));
+                }
+                splat(current.1, current.0 * ((1. - accept) / y_current));
+                match proposed {
+                    Some(proposed) if rng.uniform() < accept => {
+                        current = proposed;
+                        sampler.accept();
+                    }
+                    _ => sampler.reject(),
+                }
+            }
+        }
+
+        // dark surfaces get no splats, so pixels whose samples all miss too
+        // show the background
+        let [open, close] = projection.shutter();
+        let samples = scene.samples.max(1);
+        for (i, seen) in seen.iter_mut().enumerate().filter(|(_, seen)| !**seen) {
+            let [x, y] = [i % w, i / w];
+            *seen = (0..samples).any(|sample| {
+                let [u, v] = scene.sampler([x, y], sample).uniform_2d();
+                let pixel = [x as f64 + u - 0.5, y as f64 + v - 0.5];
+                let time = open + (close - open) * (sample as f64 + 0.5) / samples as f64;
+                let ray = projection.ray(pixel, dims).with_time(time);
+                scene.hit(ray).is_some() || scene.light.hit(ray, scene.eps).is_some()
+            });
+        }
+
+        let scale = total / bootstrap as f64 * depths as f64 / samples as f64;
+        Some(
+            film.into_iter()
+                .zip(seen)
+                .map(|(colour, seen)| seen.then_some(colour * scale))
+                .collect(),
+        )
+    }
+}
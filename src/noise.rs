@@ -0,0 +1,313 @@
+use crate::prop::HitRecord;
+use crate::rng;
+use crate::texture::{Texel, Texture};
+use crate::vector::Vector;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Noise {
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Basis {
+    Perlin,
+    #[default]
+    ImprovedPerlin,
+    Simplex,
+    Worley,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Octaves {
+    pub count: usize,
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Marble<T: Texel> {
+    pub noise: Noise,
+    pub values: [T; 2],
+    pub scale: f64,
+    pub turbulence: f64,
+    pub octaves: Octaves,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wood<T: Texel> {
+    pub noise: Noise,
+    pub values: [T; 2],
+    pub scale: f64,
+    pub rings: f64,
+    pub octaves: Octaves,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clouds<T: Texel> {
+    pub noise: Noise,
+    pub values: [T; 2],
+    pub scale: f64,
+    pub warp: f64,
+    pub octaves: Octaves,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stone<T: Texel> {
+    pub noise: Noise,
+    pub values: [T; 2],
+    pub scale: f64,
+    pub mortar: f64,
+}
+
+impl Default for Octaves {
+    fn default() -> Self {
+        Self {
+            count: 6,
+            lacunarity: 2.,
+            gain: 0.5,
+        }
+    }
+}
+
+fn fade(t: f64, improved: bool) -> f64 {
+    if improved {
+        t * t * t * (t * (6. * t - 15.) + 10.)
+    } else {
+        t * t * (3. - 2. * t)
+    }
+}
+
+fn dot<const N: usize>(a: [f64; N], b: [f64; N]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+impl Noise {
+    pub const fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+    fn hash<const N: usize>(&self, cell: [i64; N]) -> u64 {
+        rng::hash(self.seed, cell.map(|c| c as u64))
+    }
+    // Ken Perlin's fixed gradient set: the hypercube edge midpoints, or eight
+    // evenly spaced directions in 2D.
+    fn edge<const N: usize>(h: u64) -> [f64; N] {
+        if N == 2 {
+            let angle = (h % 8) as f64 * std::f64::consts::FRAC_PI_4;
+            return std::array::from_fn(|i| if i == 0 { angle.cos() } else { angle.sin() });
+        }
+        let corners = 1 << (N - 1);
+        let index = (h % (N * corners) as u64) as usize;
+        let (zero, signs) = (index / corners, index % corners);
+        let scale = 1. / ((N - 1) as f64).sqrt();
+        let mut bit = 0;
+        std::array::from_fn(|i| {
+            if i == zero {
+                0.
+            } else {
+                bit += 1;
+                if signs >> (bit - 1) & 1 == 0 {
+                    scale
+                } else {
+                    -scale
+                }
+            }
+        })
+    }
+    // The original noise used pseudo-random unit gradients.
+    fn random<const N: usize>(h: u64) -> [f64; N] {
+        let g: [f64; N] = std::array::from_fn(|i| ((h >> (16 * i)) & 0xffff) as f64 / 32767.5 - 1.);
+        let len = dot(g, g).sqrt();
+        if len > 0. {
+            g.map(|c| c / len)
+        } else {
+            Self::edge(h)
+        }
+    }
+    fn gradient<const N: usize>(&self, p: [f64; N], improved: bool) -> f64 {
+        let cell = p.map(|c| c.floor());
+        let f: [f64; N] = std::array::from_fn(|i| p[i] - cell[i]);
+        let w = f.map(|t| fade(t, improved));
+        let cell = cell.map(|c| c as i64);
+
+        let mut sum = 0.;
+        for corner in 0..1usize << N {
+            let bits: [usize; N] = std::array::from_fn(|i| corner >> i & 1);
+            let h = self.hash::<N>(std::array::from_fn(|i| cell[i] + bits[i] as i64));
+            let g: [f64; N] = if improved {
+                Self::edge(h)
+            } else {
+                Self::random(h)
+            };
+            let weight = (0..N)
+                .map(|i| if bits[i] == 1 { w[i] } else { 1. - w[i] })
+                .product::<f64>();
+            sum += weight * dot(g, std::array::from_fn(|i| f[i] - bits[i] as f64));
+        }
+        sum * 2. / (N as f64).sqrt()
+    }
+    pub fn perlin<const N: usize>(&self, p: [f64; N]) -> f64 {
+        self.gradient(p, false)
+    }
+    pub fn improved<const N: usize>(&self, p: [f64; N]) -> f64 {
+        self.gradient(p, true)
+    }
+    pub fn simplex<const N: usize>(&self, p: [f64; N]) -> f64 {
+        let n = N as f64;
+        let skew = ((n + 1.).sqrt() - 1.) / n;
+        let unskew = (1. - 1. / (n + 1.).sqrt()) / n;
+
+        let s = p.iter().sum::<f64>() * skew;
+        let cell = p.map(|c| (c + s).floor());
+        let t = cell.iter().sum::<f64>() * unskew;
+        let x0: [f64; N] = std::array::from_fn(|i| p[i] - (cell[i] - t));
+        let cell = cell.map(|c| c as i64);
+
+        // walk the simplex corners along the axes in decreasing order of x0
+        let mut order: [usize; N] = std::array::from_fn(|i| i);
+        order.sort_by(|&a, &b| x0[b].total_cmp(&x0[a]));
+
+        let radius = if N == 2 { 0.5 } else { 0.6 };
+        let mut offset = [0i64; N];
+        let mut sum = 0.;
+        for k in 0..=N {
+            if k > 0 {
+                offset[order[k - 1]] = 1;
+            }
+            let x: [f64; N] = std::array::from_fn(|i| x0[i] - offset[i] as f64 + k as f64 * unskew);
+            let falloff = radius - dot(x, x);
+            if falloff > 0. {
+                let h = self.hash::<N>(std::array::from_fn(|i| cell[i] + offset[i]));
+                let g: [f64; N] = Self::edge(h);
+                sum += falloff.powi(4) * dot(g, x);
+            }
+        }
+        sum * match N {
+            2 => 99.2,
+            3 => 46.,
+            _ => 48.,
+        }
+    }
+    // Distances to the nearest and second-nearest feature points of a
+    // jittered grid with one point per cell.
+    pub fn worley<const N: usize>(&self, p: [f64; N]) -> [f64; 2] {
+        let cell = p.map(|c| c.floor() as i64);
+        let mut nearest = [f64::INFINITY; 2];
+        for neighbour in 0..3usize.pow(N as u32) {
+            let other: [i64; N] = std::array::from_fn(|i| {
+                cell[i] + (neighbour / 3usize.pow(i as u32) % 3) as i64 - 1
+            });
+            let h = self.hash(other);
+            let d: [f64; N] = std::array::from_fn(|i| {
+                let jitter = rng::to_unit(rng::splitmix(h.wrapping_add(i as u64)));
+                other[i] as f64 + jitter - p[i]
+            });
+            let d = dot(d, d);
+            if d < nearest[0] {
+                nearest = [d, nearest[0]];
+            } else if d < nearest[1] {
+                nearest[1] = d;
+            }
+        }
+        nearest.map(f64::sqrt)
+    }
+    pub fn sample<const N: usize>(&self, basis: Basis, p: [f64; N]) -> f64 {
+        match basis {
+            Basis::Perlin => self.perlin(p),
+            Basis::ImprovedPerlin => self.improved(p),
+            Basis::Simplex => self.simplex(p),
+            Basis::Worley => 2. * self.worley(p)[0] - 1.,
+        }
+    }
+    fn octaves<const N: usize>(
+        &self,
+        basis: Basis,
+        p: [f64; N],
+        octaves: Octaves,
+        shape: impl Fn(f64) -> f64,
+    ) -> f64 {
+        let (mut sum, mut norm) = (0., 0.);
+        let (mut frequency, mut amplitude) = (1., 1.);
+        for octave in 0..octaves.count {
+            // decorrelate octaves so their lattices don't line up at the origin
+            let noise = Self::new(self.seed.wrapping_add(octave as u64));
+            sum += amplitude * shape(noise.sample(basis, p.map(|c| c * frequency)));
+            norm += amplitude;
+            frequency *= octaves.lacunarity;
+            amplitude *= octaves.gain;
+        }
+        if norm > 0. { sum / norm } else { 0. }
+    }
+    pub fn fbm<const N: usize>(&self, basis: Basis, p: [f64; N], octaves: Octaves) -> f64 {
+        self.octaves(basis, p, octaves, |n| n)
+    }
+    pub fn turbulence<const N: usize>(&self, basis: Basis, p: [f64; N], octaves: Octaves) -> f64 {
+        self.octaves(basis, p, octaves, f64::abs)
+    }
+    // Inigo Quilez's domain warping: fBm sampled at a point displaced by fBm.
+    pub fn warp<const N: usize>(
+        &self,
+        basis: Basis,
+        p: [f64; N],
+        strength: f64,
+        octaves: Octaves,
+    ) -> f64 {
+        const OFFSETS: [f64; 4] = [0., 5.2, 1.3, 9.7];
+        let q: [f64; N] = std::array::from_fn(|i| {
+            self.fbm(
+                basis,
+                p.map(|c| c + OFFSETS[i % 4] + 3.1 * i as f64),
+                octaves,
+            )
+        });
+        self.fbm::<N>(
+            basis,
+            std::array::from_fn(|i| p[i] + strength * q[i]),
+            octaves,
+        )
+    }
+}
+
+fn mix_values<T: Texel>([lo, hi]: [T; 2], t: f64) -> T {
+    let t = t.clamp(0., 1.);
+    lo * (1. - t) + hi * t
+}
+
+fn solid(hit: &HitRecord, scale: f64) -> [f64; 3] {
+    let Vector { x, y, z } = scale * hit.position;
+    [x, y, z]
+}
+
+impl<T: Texel> Texture<T> for Marble<T> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let p = solid(hit, self.scale);
+        let turbulence = self
+            .noise
+            .turbulence(Basis::ImprovedPerlin, p, self.octaves);
+        let veins = (p[0] + self.turbulence * turbulence).sin();
+        mix_values(self.values, 0.5 + 0.5 * veins)
+    }
+}
+
+impl<T: Texel> Texture<T> for Wood<T> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let p = solid(hit, self.scale);
+        let grain = self.noise.fbm(Basis::ImprovedPerlin, p, self.octaves);
+        let rings = (self.rings * (p[0].hypot(p[2]) + 0.2 * grain)).fract();
+        mix_values(self.values, rings * rings)
+    }
+}
+
+impl<T: Texel> Texture<T> for Clouds<T> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let p = solid(hit, self.scale);
+        let density = self.noise.warp(Basis::Simplex, p, self.warp, self.octaves);
+        mix_values(self.values, 0.5 + 0.5 * density)
+    }
+}
+
+impl<T: Texel> Texture<T> for Stone<T> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let [f1, f2] = self.noise.worley(solid(hit, self.scale));
+        mix_values(self.values, ((f2 - f1) / self.mortar).min(1.))
+    }
+}
@@ -0,0 +1,527 @@
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+use std::io::{Error, ErrorKind, Result};
+
+#[derive(Clone, Debug)]
+pub struct BezierPatch {
+    pub points: [[Vector; 4]; 4],
+    pub material: Material,
+    patch: Patch,
+}
+
+#[derive(Clone, Debug)]
+pub struct NurbsSurface {
+    degree: [usize; 2],
+    knots: [Vec<f64>; 2],
+    points: Vec<Vec<Vector>>,
+    weights: Vec<Vec<f64>>,
+    pub material: Material,
+    patches: Vec<Patch>,
+}
+
+// A rational Bézier piece in homogeneous coordinates with a min-max quadtree
+// of control hulls, built by repeated de Casteljau subdivision.
+#[derive(Clone, Debug)]
+struct Patch {
+    points: Vec<Vec<[f64; 4]>>,
+    domain: [[f64; 2]; 2],
+    levels: Vec<Vec<Aabb>>,
+}
+
+const DEPTH: usize = 4;
+
+fn lerp(a: [f64; 4], b: [f64; 4], t: f64) -> [f64; 4] {
+    [0, 1, 2, 3].map(|i| a[i] + t * (b[i] - a[i]))
+}
+
+fn project([x, y, z, w]: [f64; 4]) -> Vector {
+    Vector::new(x / w, y / w, z / w)
+}
+
+fn bernstein(n: usize, t: f64) -> (Vec<f64>, Vec<f64>) {
+    let basis = |n: usize| {
+        let mut b = vec![0.; n + 1];
+        b[0] = 1.;
+        for k in 1..=n {
+            for i in (0..=k).rev() {
+                b[i] = (1. - t) * b[i] + if i > 0 { t * b[i - 1] } else { 0. };
+            }
+        }
+        b
+    };
+    let b = basis(n);
+    let d = if n == 0 {
+        vec![0.]
+    } else {
+        let lower = basis(n - 1);
+        (0..=n)
+            .map(|i| {
+                let left = if i > 0 { lower[i - 1] } else { 0. };
+                let right = lower.get(i).copied().unwrap_or(0.);
+                n as f64 * (left - right)
+            })
+            .collect()
+    };
+    (b, d)
+}
+
+// Restricts a Bézier curve to the parameter range [t0, t1].
+fn restrict(points: &[[f64; 4]], [t0, t1]: [f64; 2]) -> Vec<[f64; 4]> {
+    let split = |points: &[[f64; 4]], t: f64, left: bool| {
+        let mut p = points.to_vec();
+        let mut out = Vec::with_capacity(p.len());
+        for k in 0..p.len() {
+            out.push(if left { p[0] } else { p[p.len() - 1 - k] });
+            for i in 0..p.len() - 1 - k {
+                p[i] = lerp(p[i], p[i + 1], t);
+            }
+        }
+        if !left {
+            out.reverse();
+        }
+        out
+    };
+    let left = split(points, t1, true);
+    if t1 > 0. {
+        split(&left, t0 / t1, false)
+    } else {
+        left
+    }
+}
+
+fn transpose<T: Copy>(grid: &[Vec<T>]) -> Vec<Vec<T>> {
+    (0..grid[0].len())
+        .map(|j| grid.iter().map(|row| row[j]).collect())
+        .collect()
+}
+
+impl Patch {
+    fn new(points: Vec<Vec<[f64; 4]>>, domain: [[f64; 2]; 2]) -> Self {
+        let n = 1 << DEPTH;
+        let range = |i: usize| [i as f64 / n as f64, (i + 1) as f64 / n as f64];
+        let mut leaves = Vec::with_capacity(n * n);
+        for j in 0..n {
+            let rows = transpose(
+                &points
+                    .iter()
+                    .map(|curve| restrict(curve, range(j)))
+                    .collect::<Vec<_>>(),
+            );
+            for i in 0..n {
+                let hull = rows
+                    .iter()
+                    .flat_map(|row| restrict(row, range(i)))
+                    .fold(Aabb::EMPTY, |acc, p| acc.include(project(p)));
+                leaves.push(hull);
+            }
+        }
+
+        let mut levels = vec![leaves];
+        for level in (0..DEPTH).rev() {
+            let size = 1 << level;
+            let below = levels.last().unwrap();
+            let nodes = (0..size * size)
+                .map(|k| {
+                    let (i, j) = (k % size, k / size);
+                    [[0, 0], [1, 0], [0, 1], [1, 1]]
+                        .into_iter()
+                        .map(|[di, dj]| below[(2 * j + dj) * 2 * size + 2 * i + di])
+                        .fold(Aabb::EMPTY, Aabb::union)
+                })
+                .collect();
+            levels.push(nodes);
+        }
+        levels.reverse();
+
+        Self {
+            points,
+            domain,
+            levels,
+        }
+    }
+    fn bounds(&self) -> Aabb {
+        self.levels[0][0]
+    }
+    fn eval(&self, [u, v]: [f64; 2]) -> [Vector; 3] {
+        let (bu, du) = bernstein(self.points.len() - 1, u);
+        let (bv, dv) = bernstein(self.points[0].len() - 1, v);
+        let mut sums = [[0.; 4]; 3];
+        for (i, row) in self.points.iter().enumerate() {
+            for (j, p) in row.iter().enumerate() {
+                for (sum, w) in sums
+                    .iter_mut()
+                    .zip([bu[i] * bv[j], du[i] * bv[j], bu[i] * dv[j]])
+                {
+                    for k in 0..4 {
+                        sum[k] += w * p[k];
+                    }
+                }
+            }
+        }
+        let [h, hu, hv] = sums;
+        let p = project(h);
+        let derivative = |d: [f64; 4]| (Vector::new(d[0], d[1], d[2]) - d[3] * p) / h[3];
+        [p, derivative(hu), derivative(hv)]
+    }
+    fn normal(&self, uv: [f64; 2]) -> Vector {
+        let [_, pu, pv] = self.eval(uv);
+        let n = pu ^ pv;
+        if n.sq() > 1e-24 {
+            n.norm()
+        } else {
+            // degenerate edges (e.g. a collapsed pole) have a zero cross product; step inwards
+            let [_, pu, pv] = self.eval(uv.map(|c| c + 1e-4 * (0.5 - c).signum()));
+            (pu ^ pv).norm()
+        }
+    }
+    // Derivatives with respect to the domain rather than the unit square.
+    fn tangents(&self, uv: [f64; 2]) -> [Vector; 2] {
+        let [_, pu, pv] = self.eval(uv);
+        let [du, dv] = self.domain.map(|[lo, hi]| hi - lo);
+        [pu / du, pv / dv]
+    }
+    fn to_domain(&self, uv: [f64; 2]) -> [f64; 2] {
+        [0, 1].map(|i| self.domain[i][0] + uv[i] * (self.domain[i][1] - self.domain[i][0]))
+    }
+    // Newton iteration on the two planes containing the ray, after Kajiya.
+    fn newton(&self, ray: Ray, eps: f64, [i, j]: [usize; 2]) -> Option<(f64, [f64; 2])> {
+        let n = (1 << DEPTH) as f64;
+        let cell = [i, j].map(|c| [c as f64 / n, (c + 1) as f64 / n]);
+        let [n1, n2] = ray.dir.orthonormal();
+        let (d1, d2) = (n1 * ray.eye, n2 * ray.eye);
+
+        let mut uv = cell.map(|[lo, hi]| 0.5 * (lo + hi));
+        for _ in 0..16 {
+            let [p, pu, pv] = self.eval(uv);
+            let f = [n1 * p - d1, n2 * p - d2];
+            let [a, b, c, d] = [n1 * pu, n1 * pv, n2 * pu, n2 * pv];
+            let det = a * d - b * c;
+            if det.abs() < 1e-14 {
+                return None;
+            }
+            let step = [(d * f[0] - b * f[1]) / det, (a * f[1] - c * f[0]) / det];
+            uv = [uv[0] - step[0], uv[1] - step[1]];
+            if step[0].abs() + step[1].abs() < 1e-10 {
+                break;
+            }
+        }
+
+        let tolerance = 1e-6;
+        let inside =
+            (0..2).all(|k| cell[k][0] - tolerance <= uv[k] && uv[k] <= cell[k][1] + tolerance);
+        let p = self.eval(uv)[0];
+        let residual = (n1 * p - d1).abs() + (n2 * p - d2).abs();
+        let t = (p - ray.eye) * ray.dir / ray.dir.sq();
+        (inside && residual < 1e-6 * (1. + p.abs()) && t >= eps)
+            .then_some((t, uv.map(|c| c.clamp(0., 1.))))
+    }
+    fn visit(
+        &self,
+        ray: Ray,
+        eps: f64,
+        level: usize,
+        [i, j]: [usize; 2],
+    ) -> Option<(f64, [f64; 2])> {
+        if level == DEPTH {
+            return self.newton(ray, eps, [i, j]);
+        }
+
+        let size = 2 << level;
+        let mut children = [[0, 0], [1, 0], [0, 1], [1, 1]]
+            .into_iter()
+            .map(|[di, dj]| [2 * i + di, 2 * j + dj])
+            .filter_map(|[ci, cj]| {
+                Some((
+                    [ci, cj],
+                    self.levels[level + 1][cj * size + ci].hit(ray, eps)?,
+                ))
+            })
+            .collect::<Vec<_>>();
+        children.sort_by(|a, b| a.1[0].total_cmp(&b.1[0]));
+
+        let mut best: Option<(f64, [f64; 2])> = None;
+        for (child, range) in children {
+            if best.is_some_and(|(t, _)| t < range[0]) {
+                break;
+            }
+            if let Some(hit) = self.visit(ray, eps, level + 1, child)
+                && best.is_none_or(|(t, _)| hit.0 < t)
+            {
+                best = Some(hit);
+            }
+        }
+        best
+    }
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<(f64, [f64; 2])> {
+        self.bounds().hit(ray, eps)?;
+        self.visit(ray, eps, 0, [0, 0])
+    }
+}
+
+impl BezierPatch {
+    pub fn new(points: [[Vector; 4]; 4], material: Material) -> Self {
+        let homogeneous = points
+            .iter()
+            .map(|row| row.iter().map(|p| [p.x, p.y, p.z, 1.]).collect())
+            .collect();
+        Self {
+            points,
+            material,
+            patch: Patch::new(homogeneous, [[0., 1.], [0., 1.]]),
+        }
+    }
+    pub fn eval(&self, uv: [f64; 2]) -> [Vector; 3] {
+        self.patch.eval(uv)
+    }
+    pub fn load_teapot(buf: &[u8], material: Material) -> Result<Vec<Self>> {
+        fn invalid(msg: &str) -> Error {
+            Error::new(ErrorKind::InvalidData, format!("invalid patch file: {msg}"))
+        }
+
+        let text = std::str::from_utf8(buf).map_err(|_| invalid("not UTF-8"))?;
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+        let mut line = || {
+            lines
+                .next()
+                .ok_or_else(|| invalid("unexpected end of file"))
+        };
+        fn numbers<T: std::str::FromStr>(line: &str) -> Result<Vec<T>> {
+            line.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse().map_err(|_| invalid("expected a number")))
+                .collect()
+        }
+        fn count(line: &str) -> Result<usize> {
+            line.parse().map_err(|_| invalid("expected a count"))
+        }
+
+        let indices = (0..count(line()?)?)
+            .map(|_| {
+                let row = numbers::<usize>(line()?)?;
+                <[usize; 16]>::try_from(row).map_err(|_| invalid("expected 16 indices per patch"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let vertices = (0..count(line()?)?)
+            .map(|_| match numbers::<f64>(line()?)?[..] {
+                // the classic data set is z-up; rotate so y is up
+                [x, y, z] => Ok(Vector::new(x, z, -y)),
+                _ => Err(invalid("expected 3 coordinates per vertex")),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        indices
+            .into_iter()
+            .map(|patch| {
+                let mut points = [[Vector::default(); 4]; 4];
+                for (k, index) in patch.into_iter().enumerate() {
+                    points[k % 4][k / 4] = *index
+                        .checked_sub(1)
+                        .and_then(|i| vertices.get(i))
+                        .ok_or_else(|| invalid("vertex index out of range"))?;
+                }
+                Ok(Self::new(points, material))
+            })
+            .collect()
+    }
+}
+
+impl Prop for BezierPatch {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let (t, uv) = self.patch.raycast(ray, eps)?;
+        let normal = self.patch.normal(uv);
+        let normal = if normal * ray.dir > 0. {
+            -normal
+        } else {
+            normal
+        };
+        Some(HitRecord {
+            uv: Some(uv),
+            tangents: Some(self.patch.tangents(uv)),
+            ..HitRecord::new(self, ray, t, normal, self.material)
+        })
+    }
+    fn bounds(&self) -> Aabb {
+        self.patch.bounds()
+    }
+}
+
+impl NurbsSurface {
+    pub fn new(
+        degree: [usize; 2],
+        knots: [Vec<f64>; 2],
+        points: Vec<Vec<Vector>>,
+        weights: Vec<Vec<f64>>,
+        material: Material,
+    ) -> Self {
+        let counts = [points.len(), points[0].len()];
+        for k in 0..2 {
+            let [p, n, u] = [degree[k], counts[k], knots[k].len()];
+            assert!(
+                p >= 1 && n > p,
+                "NURBS needs more control points than its degree"
+            );
+            assert_eq!(u, n + p + 1, "NURBS knot vector length mismatch");
+            assert!(knots[k].is_sorted(), "NURBS knots must be non-decreasing");
+            let clamped = |end: &[f64]| end.iter().all(|&x| x == end[0]);
+            assert!(
+                clamped(&knots[k][..=p]) && clamped(&knots[k][u - p - 1..]),
+                "NURBS knot vector must be clamped"
+            );
+        }
+        assert!(
+            points.iter().all(|row| row.len() == counts[1])
+                && weights.len() == counts[0]
+                && weights.iter().all(|row| row.len() == counts[1]),
+            "NURBS control net must be rectangular"
+        );
+        assert!(
+            weights.iter().flatten().all(|&w| w > 0.),
+            "NURBS weights must be positive"
+        );
+
+        let homogeneous = points
+            .iter()
+            .zip(&weights)
+            .map(|(row, ws)| {
+                row.iter()
+                    .zip(ws)
+                    .map(|(p, &w)| [w * p.x, w * p.y, w * p.z, w])
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+        let (u_spans, net) = Self::decompose(&knots[0], degree[0], &homogeneous);
+        let (v_spans, net) = Self::decompose(&knots[1], degree[1], &transpose(&net));
+        let net = transpose(&net);
+
+        let [p, q] = degree;
+        let mut patches = Vec::with_capacity(u_spans.len() * v_spans.len());
+        for (a, &du) in u_spans.iter().enumerate() {
+            for (b, &dv) in v_spans.iter().enumerate() {
+                let piece = net[a * p..=a * p + p]
+                    .iter()
+                    .map(|row| row[b * q..=b * q + q].to_vec())
+                    .collect();
+                patches.push(Patch::new(piece, [du, dv]));
+            }
+        }
+
+        Self {
+            degree,
+            knots,
+            points,
+            weights,
+            material,
+            patches,
+        }
+    }
+    pub const fn degree(&self) -> [usize; 2] {
+        self.degree
+    }
+    pub const fn knots(&self) -> &[Vec<f64>; 2] {
+        &self.knots
+    }
+    pub fn points(&self) -> &[Vec<Vector>] {
+        &self.points
+    }
+    pub fn weights(&self) -> &[Vec<f64>] {
+        &self.weights
+    }
+    // Boehm knot insertion along the first index until every interior knot has
+    // multiplicity equal to the degree, leaving one Bézier segment per span.
+    fn decompose(
+        knots: &[f64],
+        p: usize,
+        net: &[Vec<[f64; 4]>],
+    ) -> (Vec<[f64; 2]>, Vec<Vec<[f64; 4]>>) {
+        let mut knots = knots.to_vec();
+        let mut net = net.to_vec();
+        let mut k = p + 1;
+        while k < knots.len() - p - 1 {
+            let u = knots[k];
+            let multiplicity = knots.iter().filter(|&&x| x == u).count();
+            if multiplicity >= p {
+                k += multiplicity;
+                continue;
+            }
+            let span = knots.iter().rposition(|&x| x <= u).unwrap();
+            let mut refined = Vec::with_capacity(net.len() + 1);
+            for i in 0..=net.len() {
+                refined.push(if i + p <= span {
+                    net[i].clone()
+                } else if i > span {
+                    net[i - 1].clone()
+                } else {
+                    let a = (u - knots[i]) / (knots[i + p] - knots[i]);
+                    net[i - 1]
+                        .iter()
+                        .zip(&net[i])
+                        .map(|(&l, &r)| lerp(l, r, a))
+                        .collect()
+                });
+            }
+            net = refined;
+            knots.insert(span + 1, u);
+        }
+
+        let mut breaks = knots[p..knots.len() - p].to_vec();
+        breaks.dedup();
+        let spans = breaks.windows(2).map(|w| [w[0], w[1]]).collect();
+        (spans, net)
+    }
+    fn de_boor(knots: &[f64], p: usize, points: &[[f64; 4]], t: f64) -> [f64; 4] {
+        let n = points.len();
+        let k = knots[p..n].partition_point(|&x| x <= t).saturating_sub(1) + p;
+        let mut d = (0..=p).map(|j| points[j + k - p]).collect::<Vec<_>>();
+        for r in 1..=p {
+            for j in (r..=p).rev() {
+                let (lo, hi) = (knots[j + k - p], knots[j + 1 + k - r]);
+                let alpha = if hi > lo { (t - lo) / (hi - lo) } else { 0. };
+                d[j] = lerp(d[j - 1], d[j], alpha);
+            }
+        }
+        d[p]
+    }
+    pub fn point(&self, [u, v]: [f64; 2]) -> Vector {
+        let [p, q] = self.degree;
+        let rows = self
+            .points
+            .iter()
+            .zip(&self.weights)
+            .map(|(row, ws)| {
+                let row = row
+                    .iter()
+                    .zip(ws)
+                    .map(|(p, &w)| [w * p.x, w * p.y, w * p.z, w])
+                    .collect::<Vec<_>>();
+                Self::de_boor(&self.knots[1], q, &row, v)
+            })
+            .collect::<Vec<_>>();
+        project(Self::de_boor(&self.knots[0], p, &rows, u))
+    }
+}
+
+impl Prop for NurbsSurface {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let (patch, t, uv) = self
+            .patches
+            .iter()
+            .filter_map(|patch| patch.raycast(ray, eps).map(|(t, uv)| (patch, t, uv)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+        let normal = patch.normal(uv);
+        let normal = if normal * ray.dir > 0. {
+            -normal
+        } else {
+            normal
+        };
+        Some(HitRecord {
+            uv: Some(patch.to_domain(uv)),
+            tangents: Some(patch.tangents(uv)),
+            ..HitRecord::new(self, ray, t, normal, self.material)
+        })
+    }
+    fn bounds(&self) -> Aabb {
+        self.patches
+            .iter()
+            .fold(Aabb::EMPTY, |acc, patch| acc.union(patch.bounds()))
+    }
+}
@@ -0,0 +1,227 @@
+use crate::pixel::Colour;
+use crate::prop::{HitRecord, Prop, Shading};
+use crate::texture::Texture;
+use crate::vector::{Aabb, Ray, Vector};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Diffuse {
+    #[default]
+    Lambert,
+    OrenNayar,
+}
+
+// glTF's metallic-roughness model; the base colour is the hit material's
+// colour, so colour textures and per-point colours carry over.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pbr {
+    pub metallic: f64,
+    pub roughness: f64,
+    pub emissive: Colour,
+    pub diffuse: Diffuse,
+}
+
+// Metallic and roughness maps live here rather than on `Textured`, so the two
+// wrappers can nest either way round.
+#[derive(Clone, Debug)]
+pub struct Physical<P: Prop> {
+    pub prop: P,
+    pub pbr: Pbr,
+    pub metallic: Option<Arc<dyn Texture<f64>>>,
+    pub roughness: Option<Arc<dyn Texture<f64>>>,
+}
+
+// Smooth glass, which only the physically based integrators see through.
+#[derive(Clone, Copy, Debug)]
+pub struct Dielectric<P: Prop> {
+    pub prop: P,
+    pub ior: f64,
+}
+
+// Dielectrics reflect about 4% at normal incidence.
+pub const DIELECTRIC_F0: f64 = 0.04;
+
+// Roughness is clamped so mirror-like surfaces keep a finite highlight.
+pub const MIN_ROUGHNESS: f64 = 0.02;
+
+// The GGX (Trowbridge-Reitz) distribution of microfacet normals.
+pub fn ggx(cos_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let d = cos_h * cos_h * (a2 - 1.) + 1.;
+    a2 / (PI * d * d)
+}
+
+// Smith's height-correlated masking-shadowing, folded with the 4 cos_v
+// cos_l denominator of the Cook-Torrance BRDF.
+pub fn smith_ggx(cos_v: f64, cos_l: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let v = cos_l * (cos_v * cos_v * (1. - a2) + a2).sqrt();
+    let l = cos_v * (cos_l * cos_l * (1. - a2) + a2).sqrt();
+    0.5 / (v + l)
+}
+
+pub fn schlick(f0: Colour, cos: f64) -> Colour {
+    f0 + (Colour::WHITE - f0) * (1. - cos).clamp(0., 1.).powi(5)
+}
+
+// `eta` is the index beyond the surface over the index in front.
+pub fn fresnel(cos_i: f64, eta: f64) -> f64 {
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1. {
+        return 1.;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let s = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let p = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (s * s + p * p) / 2.
+}
+
+// `None` past the critical angle.
+pub fn refract(w: Vector, n: Vector, eta: f64) -> Option<Vector> {
+    let cos_i = w * n;
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    (sin2_t < 1.).then(|| (cos_i / eta - (1. - sin2_t).sqrt()) * n - w / eta)
+}
+
+impl Pbr {
+    pub const fn new(metallic: f64, roughness: f64) -> Self {
+        Self {
+            metallic,
+            roughness,
+            emissive: Colour::BLACK,
+            diffuse: Diffuse::Lambert,
+        }
+    }
+    pub const fn with_emissive(self, emissive: Colour) -> Self {
+        Self { emissive, ..self }
+    }
+    pub const fn with_diffuse(self, diffuse: Diffuse) -> Self {
+        Self { diffuse, ..self }
+    }
+    // glTF squares the perceptual roughness to get the GGX width.
+    pub fn alpha(&self) -> f64 {
+        let roughness = self.roughness.clamp(MIN_ROUGHNESS, 1.);
+        roughness * roughness
+    }
+    pub fn f0(&self, base: Colour) -> Colour {
+        Colour::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0).lerp(base, self.metallic)
+    }
+    // The BRDF for light arriving from `l` and leaving towards `v`, both unit
+    // vectors pointing away from the surface.
+    pub fn eval(&self, base: Colour, n: Vector, v: Vector, l: Vector) -> Colour {
+        let (cos_v, cos_l) = (n * v, n * l);
+        if cos_v <= 0. || cos_l <= 0. {
+            return Colour::BLACK;
+        }
+        let h = (v + l).norm();
+        let alpha = self.alpha();
+        let fresnel = schlick(self.f0(base), v * h);
+        let specular = ggx(n * h, alpha) * smith_ggx(cos_v, cos_l, alpha) * fresnel;
+
+        let albedo = (1. - self.metallic) * base / PI;
+        let diffuse = match self.diffuse {
+            Diffuse::Lambert => albedo,
+            // the qualitative model, with the roughness as the slope deviation
+            Diffuse::OrenNayar => {
+                let s2 = self.roughness * self.roughness;
+                let a = 1. - 0.5 * s2 / (s2 + 0.33);
+                let b = 0.45 * s2 / (s2 + 0.09);
+                let (pv, pl) = (v - cos_v * n, l - cos_l * n);
+                let cos_phi = if pv.sq() > 0. && pl.sq() > 0. {
+                    (pv.norm() * pl.norm()).max(0.)
+                } else {
+                    0.
+                };
+                let (sin_v, sin_l) = ((1. - cos_v * cos_v).sqrt(), (1. - cos_l * cos_l).sqrt());
+                // sin(max(θv, θl)) tan(min(θv, θl))
+                let (sin_max, tan_min) = if cos_v < cos_l {
+                    (sin_v, sin_l / cos_l)
+                } else {
+                    (sin_l, sin_v / cos_v)
+                };
+                (a + b * cos_phi * sin_max * tan_min) * albedo
+            }
+        };
+        (Colour::WHITE - fresnel) * diffuse + specular
+    }
+}
+
+impl<P: Prop> Physical<P> {
+    pub const fn new(prop: P, pbr: Pbr) -> Self {
+        Self {
+            prop,
+            pbr,
+            metallic: None,
+            roughness: None,
+        }
+    }
+    pub fn with_metallic(self, texture: impl Texture<f64>) -> Self {
+        Self {
+            metallic: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    pub fn with_roughness(self, texture: impl Texture<f64>) -> Self {
+        Self {
+            roughness: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    fn apply<'a>(&self, hit: HitRecord<'a>) -> HitRecord<'a> {
+        let [metallic, roughness] = [&self.metallic, &self.roughness]
+            .map(|texture| texture.as_ref().map(|texture| texture.sample(&hit)));
+        HitRecord {
+            shading: Shading::Pbr(Pbr {
+                metallic: metallic.unwrap_or(self.pbr.metallic),
+                roughness: roughness.unwrap_or(self.pbr.roughness),
+                ..self.pbr
+            }),
+            ..hit
+        }
+    }
+}
+
+impl<P: Prop> Prop for Physical<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.prop.raycast(ray, eps).map(|hit| self.apply(hit))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.prop
+            .raycast_all(ray, eps)
+            .into_iter()
+            .map(|hit| self.apply(hit))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.prop.bounds()
+    }
+}
+
+impl<P: Prop> Dielectric<P> {
+    pub const fn new(prop: P, ior: f64) -> Self {
+        Self { prop, ior }
+    }
+    fn apply<'a>(&self, hit: HitRecord<'a>) -> HitRecord<'a> {
+        HitRecord {
+            shading: Shading::Dielectric { ior: self.ior },
+            ..hit
+        }
+    }
+}
+
+impl<P: Prop> Prop for Dielectric<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.prop.raycast(ray, eps).map(|hit| self.apply(hit))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.prop
+            .raycast_all(ray, eps)
+            .into_iter()
+            .map(|hit| self.apply(hit))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.prop.bounds()
+    }
+}
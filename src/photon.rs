@@ -0,0 +1,611 @@
+use crate::integrator::{self, Bsdf, Integrator, PathTracer};
+use crate::pixel::Colour;
+use crate::prop::Shading;
+use crate::rng::{Sampler, Uniform};
+use crate::scene::{Projection, Scene};
+use crate::vector::{Aabb, Ray, Vector};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::PI;
+
+// Jensen's photon mapping, with caustics in a map of their own. `Scene::samples`
+// counts eye paths per pixel; the maps are shared between them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhotonMapper {
+    pub max_depth: usize,
+    pub photons: usize,
+    pub caustic_photons: usize,
+    pub nearest: usize,
+    pub max_radius: f64,
+    // with none, the global map is read where the eye's path lands
+    pub final_gather: usize,
+}
+
+// Hachisuka and Jensen's stochastic progressive photon mapping, with one pass
+// per `Scene::samples`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sppm {
+    pub max_depth: usize,
+    pub photons: usize,
+    pub radius: f64,
+    // the share of each pass's photons a pixel keeps; smaller shrinks faster
+    pub alpha: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Photon {
+    // back towards where the photon came from
+    pub dir: Vector,
+    pub power: Colour,
+}
+
+// The middle item of each range splits it on its widest axis.
+#[derive(Clone, Debug, Default)]
+pub struct KdTree<T> {
+    items: Vec<(Vector, T)>,
+    axes: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Neighbour {
+    d2: f64,
+    index: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Landing {
+    position: Vector,
+    photon: Photon,
+    bounces: usize,
+    // every bounce before it was specular
+    specular: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Visible {
+    p: Vector,
+    n: Vector,
+    wo: Vector,
+    bsdf: Bsdf,
+    beta: Colour,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Measurement {
+    radius2: f64,
+    count: f64,
+    flux: Colour,
+    direct: Colour,
+    seen: bool,
+}
+
+impl PartialEq for Neighbour {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbour {}
+
+impl PartialOrd for Neighbour {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbour {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.d2.total_cmp(&other.d2)
+    }
+}
+
+impl<T> KdTree<T> {
+    pub fn new(mut items: Vec<(Vector, T)>) -> Self {
+        let mut axes = vec![0; items.len()];
+        Self::build(&mut items, &mut axes);
+        Self { items, axes }
+    }
+    fn build(items: &mut [(Vector, T)], axes: &mut [usize]) {
+        if items.len() <= 1 {
+            return;
+        }
+        let spread = items
+            .iter()
+            .fold(Aabb::EMPTY, |acc, (p, _)| acc.include(*p))
+            .extent();
+        let axis = (0..3)
+            .max_by(|&a, &b| spread[a].total_cmp(&spread[b]))
+            .unwrap();
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+        axes[mid] = axis;
+
+        let (left, right) = items.split_at_mut(mid);
+        let (left_axes, right_axes) = axes.split_at_mut(mid);
+        Self::build(left, left_axes);
+        Self::build(&mut right[1..], &mut right_axes[1..]);
+    }
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+    pub fn within(&self, p: Vector, radius: f64, mut f: impl FnMut(Vector, &T)) {
+        self.visit(0, self.items.len(), p, radius * radius, &mut f);
+    }
+    fn visit(&self, start: usize, end: usize, p: Vector, r2: f64, f: &mut impl FnMut(Vector, &T)) {
+        if start >= end {
+            return;
+        }
+        let mid = start + (end - start) / 2;
+        let (q, ref item) = self.items[mid];
+        let delta = p[self.axes[mid]] - q[self.axes[mid]];
+        if (p - q).sq() <= r2 {
+            f(q, item);
+        }
+        if delta < 0. || delta * delta <= r2 {
+            self.visit(start, mid, p, r2, f);
+        }
+        if delta >= 0. || delta * delta <= r2 {
+            self.visit(mid + 1, end, p, r2, f);
+        }
+    }
+    pub fn nearest(&self, p: Vector, k: usize, max_radius: f64) -> Vec<(f64, &T)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        let mut r2 = max_radius * max_radius;
+        if k > 0 {
+            self.search(0, self.items.len(), p, k, &mut r2, &mut heap);
+        }
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|n| (n.d2, &self.items[n.index].1))
+            .collect()
+    }
+    fn search(
+        &self,
+        start: usize,
+        end: usize,
+        p: Vector,
+        k: usize,
+        r2: &mut f64,
+        heap: &mut BinaryHeap<Neighbour>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let mid = start + (end - start) / 2;
+        let q = self.items[mid].0;
+        let delta = p[self.axes[mid]] - q[self.axes[mid]];
+        let (near, far) = if delta < 0. {
+            ([start, mid], [mid + 1, end])
+        } else {
+            ([mid + 1, end], [start, mid])
+        };
+
+        self.search(near[0], near[1], p, k, r2, heap);
+        let d2 = (p - q).sq();
+        if d2 <= *r2 {
+            heap.push(Neighbour { d2, index: mid });
+            if heap.len() > k {
+                heap.pop();
+            }
+            // once full, only nearer items than the farthest kept matter
+            if heap.len() == k {
+                *r2 = heap.peek().map_or(*r2, |n| n.d2);
+            }
+        }
+        if delta * delta <= *r2 {
+            self.search(far[0], far[1], p, k, r2, heap);
+        }
+    }
+}
+
+impl KdTree<Photon> {
+    // Until there are `nearest` photons the disc is the whole search.
+    pub fn radiance(
+        &self,
+        bsdf: &Bsdf,
+        p: Vector,
+        n: Vector,
+        wo: Vector,
+        nearest: usize,
+        max_radius: f64,
+    ) -> Colour {
+        let found = self.nearest(p, nearest, max_radius);
+        let Some(&(farthest, _)) = found.last() else {
+            return Colour::BLACK;
+        };
+        let r2 = if found.len() < nearest {
+            max_radius * max_radius
+        } else {
+            farthest
+        };
+        let flux = found.iter().fold(Colour::BLACK, |acc, (_, photon)| {
+            acc + photon.power * bsdf.eval(n, wo, photon.dir)
+        });
+        flux / (PI * r2)
+    }
+}
+
+// `land` says whether to follow the photon further; `stream` keeps each set of
+// photons apart.
+fn shoot(
+    scene: &Scene,
+    [open, close]: [f64; 2],
+    max_depth: usize,
+    count: usize,
+    stream: usize,
+    mut land: impl FnMut(Landing) -> bool,
+) {
+    let light = &scene.light;
+    for i in 0..count {
+        // seeded apart from every pixel's samples
+        let mut sampler = Sampler::new(scene.sequence, scene.seed, [usize::MAX, stream], i, count);
+        let time = open + (close - open) * sampler.uniform();
+        let emission = light.emit(sampler.uniform_2d(), sampler.uniform_2d());
+        let pdf = emission.pdf_position * emission.pdf_dir;
+        if pdf <= 0. {
+            continue;
+        }
+        let cos = if emission.normal.sq() > 0. {
+            emission.normal * emission.ray.dir
+        } else {
+            1.
+        };
+        let mut power = emission.radiance * (cos / (pdf * count as f64));
+        let mut ray = emission.ray.with_time(time);
+        let mut specular = true;
+
+        for bounces in 0..max_depth {
+            let hit = scene.hit(ray);
+            // the light is opaque
+            if let Some(t) = light.hit(ray, scene.eps)
+                && hit.as_ref().is_none_or(|hit| hit.t() > t)
+            {
+                break;
+            }
+            let Some(hit) = hit else {
+                break;
+            };
+            let wo = -ray.dir.norm();
+            let n = hit.shading_normal();
+            let n = if hit.normal * wo < 0. { -n } else { n };
+            let bsdf = Bsdf::new(&hit);
+            if !bsdf.is_delta() {
+                let landing = Landing {
+                    position: hit.position,
+                    photon: Photon { dir: wo, power },
+                    bounces,
+                    specular,
+                };
+                if !land(landing) {
+                    break;
+                }
+                specular = false;
+            }
+
+            let Some(scatter) = bsdf.scatter(n, wo, &mut sampler) else {
+                break;
+            };
+            let weight = scatter.weight;
+            let survival = weight.r.max(weight.g).max(weight.b).min(1.);
+            if sampler.uniform() >= survival {
+                break;
+            }
+            power *= weight / survival;
+            ray = Ray::new(hit.position, scatter.dir).with_time(time);
+        }
+    }
+}
+
+// Follows specular bounces to the first surface that isn't; `None` if the ray
+// leaves the scene at once.
+fn visible(
+    scene: &Scene,
+    mut ray: Ray,
+    sampler: &mut impl Uniform,
+    max_depth: usize,
+    see_light: bool,
+) -> Option<(Colour, Option<Visible>)> {
+    let light = &scene.light;
+    let mut radiance = Colour::BLACK;
+    let mut beta = Colour::WHITE;
+    for depth in 0..max_depth {
+        let hit = scene.hit(ray);
+        if let Some(t) = light.hit(ray, scene.eps)
+            && hit.as_ref().is_none_or(|hit| hit.t() > t)
+        {
+            if see_light {
+                radiance += beta * light.radiance();
+            }
+            return Some((radiance, None));
+        }
+        let Some(hit) = hit else {
+            return (depth > 0).then_some((radiance, None));
+        };
+
+        let wo = -ray.dir.norm();
+        let n = hit.shading_normal();
+        let n = if hit.normal * wo < 0. { -n } else { n };
+        if let Shading::Pbr(pbr) = hit.shading {
+            radiance += beta * pbr.emissive;
+        }
+        let bsdf = Bsdf::new(&hit);
+        if !bsdf.is_delta() {
+            let p = hit.position;
+            return Some((
+                radiance,
+                Some(Visible {
+                    p,
+                    n,
+                    wo,
+                    bsdf,
+                    beta,
+                }),
+            ));
+        }
+        let Some(scatter) = bsdf.scatter(n, wo, sampler) else {
+            break;
+        };
+        beta *= scatter.weight;
+        ray = integrator::next_ray(&hit, &bsdf, scatter.dir);
+    }
+    Some((radiance, None))
+}
+
+impl Visible {
+    fn direct(&self, scene: &Scene, time: f64, sampler: &mut impl Uniform) -> Colour {
+        let (p, n) = (self.p, self.n);
+        match scene.light.sample(p, sampler.uniform_2d()) {
+            Some(sample)
+                if n * sample.dir > 0.
+                    && !scene.occluded(p, p + sample.distance * sample.dir, time) =>
+            {
+                (n * sample.dir) * self.bsdf.eval(n, self.wo, sample.dir) * sample.value
+            }
+            _ => Colour::BLACK,
+        }
+    }
+}
+
+impl PhotonMapper {
+    pub const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            photons: 100_000,
+            caustic_photons: 100_000,
+            nearest: 100,
+            max_radius: 1.,
+            final_gather: 8,
+        }
+    }
+    // Gathering from a bounce away needs every photon in the global map.
+    fn maps(&self, scene: &Scene, shutter: [f64; 2]) -> [KdTree<Photon>; 2] {
+        let mut caustic = Vec::new();
+        shoot(
+            scene,
+            shutter,
+            self.max_depth,
+            self.caustic_photons,
+            0,
+            |landing| {
+                if landing.specular && landing.bounces > 0 {
+                    caustic.push((landing.position, landing.photon));
+                }
+                false
+            },
+        );
+        let mut global = Vec::new();
+        shoot(scene, shutter, self.max_depth, self.photons, 1, |landing| {
+            if self.final_gather > 0 || !landing.specular {
+                global.push((landing.position, landing.photon));
+            }
+            true
+        });
+        [KdTree::new(caustic), KdTree::new(global)]
+    }
+    fn estimate(
+        &self,
+        scene: &Scene,
+        [caustic, global]: &[KdTree<Photon>; 2],
+        at: &Visible,
+        time: f64,
+        sampler: &mut impl Uniform,
+    ) -> Colour {
+        let read = |map: &KdTree<Photon>, v: &Visible| {
+            map.radiance(&v.bsdf, v.p, v.n, v.wo, self.nearest, self.max_radius)
+        };
+        let mut radiance = at.direct(scene, time, sampler) + read(caustic, at);
+        if self.final_gather == 0 {
+            return radiance + read(global, at);
+        }
+        for _ in 0..self.final_gather {
+            let Some(scatter) = at.bsdf.scatter(at.n, at.wo, sampler) else {
+                continue;
+            };
+            // the light itself is counted by sampling it and by the caustic map
+            let ray = Ray::new(at.p, scatter.dir).with_time(time);
+            if let Some((emitted, next)) = visible(scene, ray, sampler, self.max_depth, false) {
+                let gathered =
+                    next.map_or(emitted, |next| emitted + next.beta * read(global, &next));
+                radiance += scatter.weight * gathered / self.final_gather as f64;
+            }
+        }
+        radiance
+    }
+}
+
+impl Default for PhotonMapper {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn radiance(&self, scene: &Scene, ray: Ray, sampler: &mut Sampler) -> Option<Colour> {
+        PathTracer::new(self.max_depth).radiance(scene, ray, sampler)
+    }
+    fn render(
+        &self,
+        scene: &Scene,
+        projection: &dyn Projection,
+        dims @ [w, h]: [usize; 2],
+    ) -> Option<Vec<Option<Colour>>> {
+        let [open, close] = projection.shutter();
+        let samples = scene.samples.max(1);
+        let maps = self.maps(scene, [open, close]);
+
+        let mut film = Vec::with_capacity(w * h);
+        for (y, x) in (0..h).flat_map(|y| (0..w).map(move |x| (y, x))) {
+            let mut sum = Colour::BLACK;
+            let mut seen = false;
+            for i in 0..samples {
+                let time = open + (close - open) * (i as f64 + 0.5) / samples as f64;
+                let mut sampler = scene.sampler([x, y], i);
+                let [u, v] = sampler.uniform_2d();
+                let pixel = [x as f64 + u - 0.5, y as f64 + v - 0.5];
+                let ray = projection.ray(pixel, dims).with_time(time);
+                if let Some((radiance, at)) =
+                    visible(scene, ray, &mut sampler, self.max_depth, true)
+                {
+                    sum += radiance;
+                    if let Some(at) = at {
+                        sum += at.beta * self.estimate(scene, &maps, &at, time, &mut sampler);
+                    }
+                    seen = true;
+                }
+            }
+            film.push(seen.then_some(sum / samples as f64));
+        }
+        Some(film)
+    }
+}
+
+impl Sppm {
+    pub const fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            photons: 100_000,
+            radius: 0.25,
+            alpha: 2. / 3.,
+        }
+    }
+}
+
+impl Default for Sppm {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl Integrator for Sppm {
+    fn radiance(&self, scene: &Scene, ray: Ray, sampler: &mut Sampler) -> Option<Colour> {
+        PathTracer::new(self.max_depth).radiance(scene, ray, sampler)
+    }
+    fn render(
+        &self,
+        scene: &Scene,
+        projection: &dyn Projection,
+        dims @ [w, h]: [usize; 2],
+    ) -> Option<Vec<Option<Colour>>> {
+        let [open, close] = projection.shutter();
+        let passes = scene.samples.max(1);
+        let mut pixels = vec![
+            Measurement {
+                radius2: self.radius * self.radius,
+                count: 0.,
+                flux: Colour::BLACK,
+                direct: Colour::BLACK,
+                seen: false,
+            };
+            w * h
+        ];
+
+        for pass in 0..passes {
+            let time = open + (close - open) * (pass as f64 + 0.5) / passes as f64;
+            let mut points = vec![None; w * h];
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let (x, y) = (i % w, i / w);
+                let mut sampler = scene.sampler([x, y], pass);
+                let [u, v] = sampler.uniform_2d();
+                let ray = projection
+                    .ray([x as f64 + u - 0.5, y as f64 + v - 0.5], dims)
+                    .with_time(time);
+                let Some((radiance, at)) = visible(scene, ray, &mut sampler, self.max_depth, true)
+                else {
+                    continue;
+                };
+                pixel.seen = true;
+                pixel.direct += radiance;
+                if let Some(at) = at {
+                    pixel.direct += at.beta * at.direct(scene, time, &mut sampler);
+                    points[i] = Some(at);
+                }
+            }
+
+            // photons that arrive straight from the light were sampled above
+            let reach = pixels
+                .iter()
+                .zip(&points)
+                .filter(|(_, at)| at.is_some())
+                .fold(0., |r2: f64, (pixel, _)| r2.max(pixel.radius2))
+                .sqrt();
+            let tree = KdTree::new(
+                points
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, at)| at.map(|at| (at.p, i)))
+                    .collect(),
+            );
+            let mut gathered = vec![(0_usize, Colour::BLACK); w * h];
+            shoot(
+                scene,
+                [time, time],
+                self.max_depth,
+                self.photons,
+                pass,
+                |landing| {
+                    if landing.bounces > 0 {
+                        tree.within(landing.position, reach, |p, &i| {
+                            if let Some(at) = &points[i]
+                                && (p - landing.position).sq() <= pixels[i].radius2
+                            {
+                                let photon = landing.photon;
+                                gathered[i].0 += 1;
+                                gathered[i].1 +=
+                                    photon.power * at.bsdf.eval(at.n, at.wo, photon.dir);
+                            }
+                        });
+                    }
+                    true
+                },
+            );
+
+            for ((pixel, at), (count, flux)) in pixels.iter_mut().zip(&points).zip(gathered) {
+                let Some(at) = at else {
+                    continue;
+                };
+                if count > 0 {
+                    let total = pixel.count + self.alpha * count as f64;
+                    let shrink = total / (pixel.count + count as f64);
+                    pixel.flux = (pixel.flux + at.beta * flux) * shrink;
+                    pixel.radius2 *= shrink;
+                    pixel.count = total;
+                }
+            }
+        }
+
+        Some(
+            pixels
+                .into_iter()
+                .map(|pixel| {
+                    let indirect = pixel.flux / (passes as f64 * PI * pixel.radius2);
+                    pixel
+                        .seen
+                        .then_some(pixel.direct / passes as f64 + indirect)
+                })
+                .collect(),
+        )
+    }
+}
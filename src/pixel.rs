@@ -52,6 +52,13 @@ pub struct Rgb {
     pub b: u8,
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Colour {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
 impl Rgba {
     pub const fn transparent() -> Self {
         Self {
@@ -85,6 +92,58 @@ impl Rgb {
             b: 0xff,
         }
     }
+    pub fn lerp(self, rhs: Self, t: f64) -> Self {
+        #[inline]
+        fn mix(a: u8, b: u8, t: f64) -> u8 {
+            (a as f64 + (b as f64 - a as f64) * t)
+                .round()
+                .clamp(0., 255.) as u8
+        }
+
+        Self {
+            r: mix(self.r, rhs.r, t),
+            g: mix(self.g, rhs.g, t),
+            b: mix(self.b, rhs.b, t),
+        }
+    }
+}
+
+impl Colour {
+    pub const BLACK: Self = Self::new(0., 0., 0.);
+    pub const WHITE: Self = Self::new(1., 1., 1.);
+
+    pub const fn new(r: f64, g: f64, b: f64) -> Self {
+        Self { r, g, b }
+    }
+    pub fn from_rgb(rgb: Rgb) -> Self {
+        Self::new(rgb.r as f64, rgb.g as f64, rgb.b as f64) / 255.
+    }
+    pub fn to_rgb(self) -> Rgb {
+        let quantise = |c: f64| (c * 255.).round().clamp(0., 255.) as u8;
+        Rgb {
+            r: quantise(self.r),
+            g: quantise(self.g),
+            b: quantise(self.b),
+        }
+    }
+    pub const fn luminance(self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+    pub fn lerp(self, rhs: Self, t: f64) -> Self {
+        self + (rhs - self) * t
+    }
+}
+
+impl From<Rgb> for Colour {
+    fn from(rgb: Rgb) -> Self {
+        Self::from_rgb(rgb)
+    }
+}
+
+impl From<Colour> for Rgb {
+    fn from(colour: Colour) -> Self {
+        colour.to_rgb()
+    }
 }
 
 impl Pixel for Rgba {
@@ -442,3 +501,75 @@ impl Div<Rgb> for f64 {
         rhs / self
     }
 }
+
+impl Add for Colour {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.r + rhs.r, self.g + rhs.g, self.b + rhs.b)
+    }
+}
+
+impl AddAssign for Colour {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Colour {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.r - rhs.r, self.g - rhs.g, self.b - rhs.b)
+    }
+}
+
+impl SubAssign for Colour {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul for Colour {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(self.r * rhs.r, self.g * rhs.g, self.b * rhs.b)
+    }
+}
+
+impl MulAssign for Colour {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl Mul<f64> for Colour {
+    type Output = Self;
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self::new(self.r * rhs, self.g * rhs, self.b * rhs)
+    }
+}
+
+impl MulAssign<f64> for Colour {
+    fn mul_assign(&mut self, rhs: f64) {
+        *self = *self * rhs;
+    }
+}
+
+impl Mul<Colour> for f64 {
+    type Output = Colour;
+    fn mul(self, rhs: Colour) -> Self::Output {
+        rhs.mul(self)
+    }
+}
+
+impl Div<f64> for Colour {
+    type Output = Self;
+    fn div(self, rhs: f64) -> Self::Output {
+        Self::new(self.r / rhs, self.g / rhs, self.b / rhs)
+    }
+}
+
+impl DivAssign<f64> for Colour {
+    fn div_assign(&mut self, rhs: f64) {
+        *self = *self / rhs;
+    }
+}
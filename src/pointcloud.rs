@@ -0,0 +1,183 @@
+use crate::bvh::Bvh;
+use crate::pixel::Rgb;
+use crate::poly;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+use std::io::{Error, ErrorKind, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub position: Vector,
+    pub normal: Vector,
+    pub colour: Rgb,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Splat {
+    Disc,
+    Sphere,
+}
+
+#[derive(Clone, Debug)]
+pub struct PointCloud {
+    points: Vec<Point>,
+    bvh: Bvh,
+    radius: f64,
+    pub splat: Splat,
+    pub material: Material,
+}
+
+impl PointCloud {
+    pub const LEAF_SIZE: usize = 8;
+    // How far from a splat's surface, relative to its radius, a ray may start
+    // and still count as leaving it.
+    pub const TOLERANCE: f64 = 1e-6;
+    // Half the thickness of a disc splat, relative to its radius, within which
+    // a ray leaving the surface skips it.
+    pub const DISC_SLAB: f64 = 0.1;
+
+    pub fn new(points: Vec<Point>, radius: f64, splat: Splat, material: Material) -> Self {
+        let bvh = Bvh::from_fn(points.len(), Self::LEAF_SIZE, |i| {
+            Aabb::around(points[i].position, radius)
+        });
+        Self {
+            points,
+            bvh,
+            radius,
+            splat,
+            material,
+        }
+    }
+    // Reads whitespace-separated `x y z r g b [nx ny nz]` lines, skipping
+    // blank lines and `#` comments; points without normals face +y.
+    pub fn from_xyz(buf: &[u8], radius: f64, splat: Splat, material: Material) -> Result<Self> {
+        fn invalid(line: usize, msg: &str) -> Error {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid XYZ on line {line}: {msg}"),
+            )
+        }
+
+        let text = std::str::from_utf8(buf).map_err(|_| invalid(0, "not UTF-8"))?;
+        let points = text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.split('#').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(n, line)| {
+                let fields = line
+                    .split_whitespace()
+                    .map(|s| {
+                        s.parse::<f64>()
+                            .map_err(|_| invalid(n, "expected a number"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let (position, colour, normal) = match fields[..] {
+                    [x, y, z, r, g, b] => (Vector::new(x, y, z), [r, g, b], Vector::J),
+                    [x, y, z, r, g, b, nx, ny, nz] => {
+                        let normal = Vector::new(nx, ny, nz);
+                        let length = normal.abs();
+                        if length == 0. || !length.is_finite() {
+                            return Err(invalid(n, "zero or non-finite normal"));
+                        }
+                        (Vector::new(x, y, z), [r, g, b], normal.norm())
+                    }
+                    _ => return Err(invalid(n, "expected 6 or 9 fields")),
+                };
+                let [r, g, b] = colour.map(|c| c.clamp(0., 255.) as u8);
+                Ok(Point {
+                    position,
+                    normal,
+                    colour: Rgb { r, g, b },
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(points, radius, splat, material))
+    }
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+    pub const fn radius(&self) -> f64 {
+        self.radius
+    }
+    // Whether `p` lies on the surface of some splat.
+    fn on_surface(&self, p: Vector) -> bool {
+        let tolerance = Self::TOLERANCE * self.radius;
+        let r2 = self.radius * self.radius;
+        self.bvh
+            .find(p, |i| {
+                let point = &self.points[i];
+                let disp = p - point.position;
+                let on = match self.splat {
+                    Splat::Disc => (disp * point.normal).abs() <= tolerance && disp.sq() <= r2,
+                    Splat::Sphere => (disp.abs() - self.radius).abs() <= tolerance,
+                };
+                on.then_some(())
+            })
+            .is_some()
+    }
+    // Whether a ray leaving the surface from `p` starts on this splat: inside
+    // a sphere, or close to a disc's plane where it or a disc it overlaps
+    // covers.
+    fn starts_on(&self, point: &Point, p: Vector) -> bool {
+        let disp = p - point.position;
+        let r2 = self.radius * self.radius;
+        match self.splat {
+            Splat::Disc => {
+                let height = disp * point.normal;
+                height.abs() <= Self::DISC_SLAB * self.radius
+                    && disp.sq() - height * height <= 4. * r2
+            }
+            Splat::Sphere => disp.sq() <= r2,
+        }
+    }
+    fn intersect(&self, point: &Point, ray: Ray, eps: f64) -> Option<(f64, Vector)> {
+        let disp = point.position - ray.eye;
+        match self.splat {
+            Splat::Disc => {
+                let facing = point.normal * ray.dir;
+                if facing == 0. {
+                    return None;
+                }
+                let t = disp * point.normal / facing;
+                let normal = if facing > 0. {
+                    -point.normal
+                } else {
+                    point.normal
+                };
+                (t >= eps && (ray.at(t) - point.position).sq() <= self.radius * self.radius)
+                    .then_some((t, normal))
+            }
+            Splat::Sphere => {
+                let t = poly::sphere(-disp, ray.dir, self.radius)?
+                    .into_iter()
+                    .find(|&t| t >= eps)?;
+                Some((t, (ray.at(t) - point.position) / self.radius))
+            }
+        }
+    }
+}
+
+impl Prop for PointCloud {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        // rays leaving the surface (e.g. shadow rays) would otherwise hit the
+        // neighbouring splats that overlap the one they start on
+        let leaving = self.on_surface(ray.eye);
+        let (t, (point, normal)) = self.bvh.nearest(ray, eps, |i, _| {
+            let point = &self.points[i];
+            if leaving && self.starts_on(point, ray.eye) {
+                return None;
+            }
+            let (t, normal) = self.intersect(point, ray, eps)?;
+            Some((t, (point, normal)))
+        })?;
+        let material = Material {
+            colour: point.colour,
+            ..self.material
+        };
+        Some(HitRecord::new(self, ray, t, normal, material))
+    }
+    fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+}
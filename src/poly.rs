@@ -0,0 +1,219 @@
+use crate::vector::Vector;
+
+// The real roots in ascending order, of which there is one if `a` is zero.
+pub fn quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0. {
+        return if b == 0. { Vec::new() } else { vec![-c / b] };
+    }
+    let d = b * b - 4. * a * c;
+    if d < 0. {
+        return Vec::new();
+    }
+    // avoid subtracting nearly equal quantities by deriving the smaller root from the product
+    let q = -0.5 * (b + d.sqrt().copysign(b));
+    let t1 = q / a;
+    let t2 = if q == 0. { t1 } else { c / q };
+    if t1 <= t2 { vec![t1, t2] } else { vec![t2, t1] }
+}
+
+// Where the ray eye + t dir meets a sphere, given the eye's displacement from
+// its centre. The discriminant comes from the ray's closest approach rather
+// than b² - 4ac, which cancels badly for small or distant spheres.
+pub fn sphere(disp: Vector, dir: Vector, radius: f64) -> Option<[f64; 2]> {
+    let a = dir.sq();
+    let b = disp * dir;
+    let miss = disp - b / a * dir;
+    let d = a * (radius * radius - miss.sq());
+    if a == 0. || d < 0. {
+        return None;
+    }
+    let q = -(b + d.sqrt().copysign(b));
+    let t1 = q / a;
+    let t2 = if q == 0. {
+        t1
+    } else {
+        (disp.sq() - radius * radius) / q
+    };
+    Some(if t1 <= t2 { [t1, t2] } else { [t2, t1] })
+}
+
+pub fn cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0. {
+        return quadratic(b, c, d);
+    }
+    let (b, c, d) = (b / a, c / a, d / a);
+
+    let p = c - b * b / 3.;
+    let q = 2. * b * b * b / 27. - b * c / 3. + d;
+    let shift = -b / 3.;
+
+    let disc = q * q / 4. + p * p * p / 27.;
+    let mut roots = if p == 0. && q == 0. {
+        vec![shift]
+    } else if disc > 0. {
+        let s = disc.sqrt();
+        vec![(-q / 2. + s).cbrt() + (-q / 2. - s).cbrt() + shift]
+    } else if p == 0. {
+        vec![(-q).cbrt() + shift]
+    } else {
+        let r = 2. * (-p / 3.).sqrt();
+        let phi = (3. * q / (p * r)).clamp(-1., 1.).acos() / 3.;
+        (0..3)
+            .map(|k| r * (phi - 2. * std::f64::consts::PI * k as f64 / 3.).cos() + shift)
+            .collect()
+    };
+
+    for x in &mut roots {
+        *x = polish(&[1., b, c, d], *x);
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+pub fn quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0. {
+        return cubic(b, c, d, e);
+    }
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+
+    let shift = -b / 4.;
+    let p = c - 3. * b * b / 8.;
+    let q = d - b * c / 2. + b * b * b / 8.;
+    let r = e - b * d / 4. + b * b * c / 16. - 3. * b * b * b * b / 256.;
+
+    // q has the dimensions of a cubed root, so compare it with the cube of
+    // the roots' scale
+    let scale = p.abs().sqrt().max(r.abs().sqrt().sqrt());
+    let mut roots = Vec::with_capacity(4);
+    if q.abs() <= 1e-12 * scale * scale * scale {
+        for z in quadratic(1., p, r) {
+            if z >= 0. {
+                roots.extend([z.sqrt(), -z.sqrt()]);
+            }
+        }
+    } else {
+        let m = cubic(8., 8. * p, 2. * p * p - 8. * r, -q * q)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0. {
+            return Vec::new();
+        }
+        let s = (2. * m).sqrt();
+        let k = q / (2. * s);
+        for (sign, offset) in [(1., -k), (-1., k)] {
+            roots.extend(quadratic(1., sign * s, p / 2. + m + offset));
+        }
+    }
+
+    let mut roots = roots
+        .into_iter()
+        .map(|y| polish(&[1., b, c, d, e], y + shift))
+        .collect::<Vec<_>>();
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+pub fn eval(coeffs: &[f64], x: f64) -> f64 {
+    coeffs.iter().fold(0., |acc, &c| acc * x + c)
+}
+
+pub fn derivative(coeffs: &[f64], x: f64) -> f64 {
+    let n = coeffs.len().saturating_sub(1);
+    coeffs[..n]
+        .iter()
+        .enumerate()
+        .fold(0., |acc, (i, &c)| acc * x + (n - i) as f64 * c)
+}
+
+pub fn polish(coeffs: &[f64], mut x: f64) -> f64 {
+    for _ in 0..4 {
+        let f = eval(coeffs, x);
+        let df = derivative(coeffs, x);
+        if f == 0. || df == 0. {
+            break;
+        }
+        let next = x - f / df;
+        if !next.is_finite() || eval(coeffs, next).abs() >= f.abs() {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "{roots:?} != {expected:?}");
+        for (root, want) in roots.iter().zip(expected) {
+            assert!(
+                (root - want).abs() <= 1e-9 * want.abs(),
+                "{roots:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(quadratic(1., -3., 2.), &[1., 2.]);
+        assert_roots(quadratic(1., 0., 1.), &[]);
+        assert_roots(quadratic(0., 2., -4.), &[2.]);
+        assert_roots(quadratic(0., 0., 1.), &[]);
+        assert_roots(quadratic(1., -1e8, 1.), &[1e-8, 1e8]);
+    }
+
+    #[test]
+    fn sphere_roots() {
+        let hit = sphere(Vector::new(0., 0., -5.), Vector::new(0., 0., 2.), 1.);
+        assert_eq!(hit, Some([2., 3.]));
+        assert_eq!(
+            sphere(Vector::new(2., 0., -5.), Vector::new(0., 0., 1.), 1.),
+            None
+        );
+
+        // a tiny sphere far away, where b² - 4ac loses every digit
+        let [t1, t2] = sphere(Vector::new(0., 1e-5, -1e5), Vector::new(0., 0., 1.), 1e-4).unwrap();
+        let half = (1e-8f64 - 1e-10).sqrt();
+        assert!((t1 - (1e5 - half)).abs() < 1e-9 && (t2 - (1e5 + half)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(cubic(1., -6., 11., -6.), &[1., 2., 3.]);
+        // 2(x + 1)(x² + 1)
+        assert_roots(cubic(2., 2., 2., 2.), &[-1.]);
+        // (x - 2)³
+        assert_roots(cubic(1., -6., 12., -8.), &[2.]);
+        // x³ - 8
+        assert_roots(cubic(1., 0., 0., -8.), &[2.]);
+        // a vanishing leading coefficient falls back to the quadratic
+        assert_roots(cubic(0., 1., -3., 2.), &[1., 2.]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(quartic(1., -10., 35., -50., 24.), &[1., 2., 3., 4.]);
+        // (x² - 1)(x² - 4), which is biquadratic
+        assert_roots(quartic(1., 0., -5., 0., 4.), &[-2., -1., 1., 2.]);
+        // x⁴ + 1
+        assert_roots(quartic(1., 0., 0., 0., 1.), &[]);
+        // (x - 1)(x + 1)(x² + 1)
+        assert_roots(quartic(3., 0., 0., 0., -3.), &[-1., 1.]);
+        // (x - 100)(x - 200)(x - 300)(x - 400), whose depressed q is large
+        // in absolute terms but not relative to the roots
+        assert_roots(
+            quartic(1., -1e3, 3.5e5, -5e7, 2.4e9),
+            &[100., 200., 300., 400.],
+        );
+        // (x - 1e-5)(x - 2e-5)(x - 3e-5)(x - 1e-4), whose depressed q is
+        // below any fixed threshold without being negligible
+        assert_roots(
+            quartic(1., -1.6e-4, 7.1e-9, -1.16e-13, 6e-19),
+            &[1e-5, 2e-5, 3e-5, 1e-4],
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use crate::poly;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+
+#[derive(Clone, Copy, Debug)]
+pub struct AxisBox {
+    pub min: Vector,
+    pub max: Vector,
+    pub material: Material,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct OrientedBox {
+    pub centre: Vector,
+    pub axes: [Vector; 3],
+    pub half: Vector,
+    pub material: Material,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cylinder {
+    pub base: Vector,
+    pub axis: Vector,
+    pub radius: f64,
+    pub capped: bool,
+    pub material: Material,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cone {
+    pub base: Vector,
+    pub axis: Vector,
+    pub radius: [f64; 2],
+    pub capped: bool,
+    pub material: Material,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Torus {
+    pub centre: Vector,
+    pub axis: Vector,
+    pub major: f64,
+    pub minor: f64,
+    pub material: Material,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Capsule {
+    pub start: Vector,
+    pub end: Vector,
+    pub radius: f64,
+    pub material: Material,
+}
+
+fn hits<'a>(
+    prop: &'a dyn Prop,
+    ray: Ray,
+    eps: f64,
+    material: Material,
+    mut crossings: Vec<(f64, Vector)>,
+) -> Vec<HitRecord<'a>> {
+    crossings.retain(|&(t, _)| t >= eps);
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+    crossings
+        .into_iter()
+        .map(|(t, normal)| HitRecord::new(prop, ray, t, normal, material))
+        .collect()
+}
+
+fn slabs(eye: Vector, dir: Vector, min: Vector, max: Vector) -> Option<[(f64, usize, f64); 2]> {
+    let mut near = (f64::NEG_INFINITY, 0, 0.);
+    let mut far = (f64::INFINITY, 0, 0.);
+    for axis in [Vector::X, Vector::Y, Vector::Z] {
+        if dir[axis] == 0. {
+            if eye[axis] < min[axis] || eye[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (min[axis] - eye[axis]) / dir[axis];
+        let t2 = (max[axis] - eye[axis]) / dir[axis];
+        let (t1, t2, sign) = if t1 <= t2 {
+            (t1, t2, -1.)
+        } else {
+            (t2, t1, 1.)
+        };
+        if t1 > near.0 {
+            near = (t1, axis, sign);
+        }
+        if t2 < far.0 {
+            far = (t2, axis, -sign);
+        }
+    }
+    (near.0 <= far.0).then_some([near, far])
+}
+
+fn disc_bounds(centre: Vector, axis: Vector, radius: f64) -> Aabb {
+    let a = axis.norm();
+    let extent = Vector::new(
+        radius * (1. - a.x * a.x).max(0.).sqrt(),
+        radius * (1. - a.y * a.y).max(0.).sqrt(),
+        radius * (1. - a.z * a.z).max(0.).sqrt(),
+    );
+    Aabb::new(centre - extent, centre + extent)
+}
+
+impl AxisBox {
+    fn crossings(&self, ray: Ray) -> Vec<(f64, Vector)> {
+        slabs(ray.eye, ray.dir, self.min, self.max)
+            .into_iter()
+            .flatten()
+            .map(|(t, axis, sign)| (t, sign * Vector::unit(axis)))
+            .collect()
+    }
+}
+
+impl Prop for AxisBox {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        hits(self, ray, eps, self.material, self.crossings(ray))
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+}
+
+impl OrientedBox {
+    pub fn new(
+        centre: Vector,
+        forward: Vector,
+        up: Vector,
+        half: Vector,
+        material: Material,
+    ) -> Self {
+        let z = forward.norm();
+        let x = (up ^ z).norm();
+        let y = z ^ x;
+        Self {
+            centre,
+            axes: [x, y, z],
+            half,
+            material,
+        }
+    }
+    fn crossings(&self, ray: Ray) -> Vec<(f64, Vector)> {
+        let disp = ray.eye - self.centre;
+        let eye = Vector::from_array(self.axes.map(|axis| disp * axis));
+        let dir = Vector::from_array(self.axes.map(|axis| ray.dir * axis));
+        slabs(eye, dir, -self.half, self.half)
+            .into_iter()
+            .flatten()
+            .map(|(t, axis, sign)| (t, sign * self.axes[axis]))
+            .collect()
+    }
+}
+
+impl Prop for OrientedBox {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        hits(self, ray, eps, self.material, self.crossings(ray))
+    }
+    fn bounds(&self) -> Aabb {
+        let extent = Vector::new(
+            (0..3).map(|i| (self.axes[i].x * self.half[i]).abs()).sum(),
+            (0..3).map(|i| (self.axes[i].y * self.half[i]).abs()).sum(),
+            (0..3).map(|i| (self.axes[i].z * self.half[i]).abs()).sum(),
+        );
+        Aabb::new(self.centre - extent, self.centre + extent)
+    }
+}
+
+impl Cylinder {
+    fn crossings(&self, ray: Ray) -> Vec<(f64, Vector)> {
+        Cone {
+            base: self.base,
+            axis: self.axis,
+            radius: [self.radius; 2],
+            capped: self.capped,
+            material: self.material,
+        }
+        .crossings(ray)
+    }
+}
+
+impl Prop for Cylinder {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        hits(self, ray, eps, self.material, self.crossings(ray))
+    }
+    fn bounds(&self) -> Aabb {
+        disc_bounds(self.base, self.axis, self.radius).union(disc_bounds(
+            self.base + self.axis,
+            self.axis,
+            self.radius,
+        ))
+    }
+}
+
+impl Cone {
+    fn crossings(&self, ray: Ray) -> Vec<(f64, Vector)> {
+        let height = self.axis.abs();
+        let a = self.axis / height;
+        let [r0, r1] = self.radius;
+        let k = (r1 - r0) / height;
+
+        let disp = ray.eye - self.base;
+        let (oa, da) = (disp * a, ray.dir * a);
+        let (op, dp) = (disp - oa * a, ray.dir - da * a);
+        let r = r0 + k * oa;
+
+        let mut crossings = Vec::with_capacity(4);
+        for t in poly::quadratic(
+            dp.sq() - k * k * da * da,
+            2. * (op * dp - k * r * da),
+            op.sq() - r * r,
+        ) {
+            let s = oa + t * da;
+            if (0. ..=height).contains(&s) {
+                let p = op + t * dp;
+                let normal = (p - (r0 + k * s) * k * a).norm();
+                crossings.push((t, normal));
+            }
+        }
+        if self.capped && da != 0. {
+            for (s, radius, normal) in [(0., r0, -a), (height, r1, a)] {
+                let t = (s - oa) / da;
+                if radius > 0. && (op + t * dp).sq() <= radius * radius {
+                    crossings.push((t, normal));
+                }
+            }
+        }
+        crossings
+    }
+}
+
+impl Prop for Cone {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        hits(self, ray, eps, self.material, self.crossings(ray))
+    }
+    fn bounds(&self) -> Aabb {
+        disc_bounds(self.base, self.axis, self.radius[0]).union(disc_bounds(
+            self.base + self.axis,
+            self.axis,
+            self.radius[1],
+        ))
+    }
+}
+
+impl Capsule {
+    fn closest(&self, p: Vector) -> Vector {
+        let axis = self.end - self.start;
+        let s = ((p - self.start) * axis / axis.sq()).clamp(0., 1.);
+        self.start + s * axis
+    }
+    fn crossings(&self, ray: Ray) -> Vec<(f64, Vector)> {
+        let axis = self.end - self.start;
+        let height = axis.abs();
+        let a = axis / height;
+        let r2 = self.radius * self.radius;
+
+        let disp = ray.eye - self.start;
+        let (oa, da) = (disp * a, ray.dir * a);
+        let (op, dp) = (disp - oa * a, ray.dir - da * a);
+
+        let mut intervals = Vec::with_capacity(3);
+        if let [t1, t2] = poly::quadratic(dp.sq(), 2. * (op * dp), op.sq() - r2)[..] {
+            let [s1, s2] = if da == 0. {
+                if (0. ..=height).contains(&oa) {
+                    [f64::NEG_INFINITY, f64::INFINITY]
+                } else {
+                    [f64::INFINITY, f64::NEG_INFINITY]
+                }
+            } else {
+                let s1 = -oa / da;
+                let s2 = (height - oa) / da;
+                [s1.min(s2), s1.max(s2)]
+            };
+            intervals.push([t1.max(s1), t2.min(s2)]);
+        }
+        for centre in [self.start, self.end] {
+            intervals.extend(poly::sphere(ray.eye - centre, ray.dir, self.radius));
+        }
+
+        let [t1, t2] = intervals
+            .into_iter()
+            .filter(|[t1, t2]| t1 <= t2)
+            .fold([f64::INFINITY, f64::NEG_INFINITY], |[a, b], [c, d]| {
+                [a.min(c), b.max(d)]
+            });
+        if t1 <= t2 {
+            [t1, t2]
+                .into_iter()
+                .map(|t| {
+                    let p = ray.at(t);
+                    (t, (p - self.closest(p)) / self.radius)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl Prop for Capsule {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        hits(self, ray, eps, self.material, self.crossings(ray))
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.start, self.radius).union(Aabb::around(self.end, self.radius))
+    }
+}
+
+impl Torus {
+    fn crossings(&self, ray: Ray) -> Vec<(f64, Vector)> {
+        let axis = self.axis.norm();
+        let [u, v] = axis.orthonormal();
+        let len = ray.dir.abs();
+        let disp = ray.eye - self.centre;
+        let o = Vector::new(disp * u, disp * v, disp * axis);
+        let d = Vector::new(ray.dir * u, ray.dir * v, ray.dir * axis) / len;
+
+        let (rr, r2) = (self.major * self.major, self.minor * self.minor);
+        let e = o.sq() - rr - r2;
+        let f = o * d;
+
+        poly::quartic(
+            1.,
+            4. * f,
+            2. * e + 4. * f * f + 4. * rr * d.z * d.z,
+            4. * f * e + 8. * rr * o.z * d.z,
+            e * e - 4. * rr * (r2 - o.z * o.z),
+        )
+        .into_iter()
+        .map(|s| {
+            let p = o + s * d;
+            let ring = Vector::new(p.x, p.y, 0.).norm() * self.major;
+            let n = (p - ring) / self.minor;
+            (s / len, n.x * u + n.y * v + n.z * axis)
+        })
+        .collect()
+    }
+}
+
+impl Prop for Torus {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        hits(self, ray, eps, self.material, self.crossings(ray))
+    }
+    fn bounds(&self) -> Aabb {
+        let ring = disc_bounds(self.centre, self.axis, self.major);
+        let pad = Vector::new(self.minor, self.minor, self.minor);
+        Aabb::new(ring.min - pad, ring.max + pad)
+    }
+}
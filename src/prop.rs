@@ -1,5 +1,9 @@
+use crate::matrix::Transform;
+use crate::pbr::Pbr;
 use crate::pixel::Rgb;
-use crate::vector::{Ray, Vector};
+use crate::poly;
+use crate::vector::{Aabb, Differentials, Ray, Vector};
+use std::sync::Arc;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Material {
@@ -17,11 +21,42 @@ pub struct HitRecord<'a> {
     pub distance: f64,
     pub position: Vector,
     pub normal: Vector,
+    // the normal tilted by bump and normal maps; `normal` stays geometric so
+    // it still tells entering from leaving
+    pub bumped: Option<Vector>,
     pub material: Material,
+    pub uv: Option<[f64; 2]>,
+    // the surface derivatives dp/du and dp/dv at `uv`, unnormalised
+    pub tangents: Option<[Vector; 2]>,
+    pub shading: Shading,
+    pub voxel: Option<[isize; 3]>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Shading {
+    #[default]
+    Phong,
+    Hair {
+        tangent: Vector,
+    },
+    Pbr(Pbr),
+    // smooth glass, tinted by the material's colour
+    Dielectric {
+        ior: f64,
+    },
 }
 
 pub trait Prop: 'static + std::fmt::Debug {
-    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord>;
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>>;
+    // Every crossing in order, alternately entering and leaving, as CSG reads
+    // them. Heightfields, patches, curves and point clouds bound no volume and
+    // keep the single-hit default, so they can't be CSG operands.
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.raycast(ray, eps).into_iter().collect()
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::INFINITE
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -31,41 +66,299 @@ pub struct Sphere {
     pub material: Material,
 }
 
-impl Prop for Sphere {
-    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord> {
-        #[inline]
-        const fn sq(f: f64) -> f64 {
-            f * f
+#[derive(Clone, Copy, Debug)]
+pub struct Transformed<P: Prop> {
+    pub prop: P,
+    pub transform: Transform,
+}
+
+pub type Instance = Transformed<Arc<dyn Prop>>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Moving<P: Prop> {
+    pub prop: P,
+    pub offset: [Vector; 2],
+    pub time: [f64; 2],
+}
+
+impl<'a> HitRecord<'a> {
+    pub fn new(prop: &'a dyn Prop, ray: Ray, t: f64, normal: Vector, material: Material) -> Self {
+        Self {
+            prop,
+            ray,
+            distance: ray.distance(t),
+            position: ray.at(t),
+            normal,
+            bumped: None,
+            material,
+            uv: None,
+            tangents: None,
+            shading: Shading::Phong,
+            voxel: None,
+        }
+    }
+    pub fn t(&self) -> f64 {
+        self.distance / self.ray.dir.abs()
+    }
+    pub fn entering(&self) -> bool {
+        self.normal * self.ray.dir < 0.
+    }
+    pub fn shading_normal(&self) -> Vector {
+        self.bumped.unwrap_or(self.normal)
+    }
+    // Where the neighbouring pixels' rays meet the tangent plane, relative to
+    // the hit position.
+    pub fn position_differentials(&self) -> Option<[Vector; 2]> {
+        let Differentials { eye, dir } = self.ray.differentials?;
+        let n = self.normal;
+        let offset = |k: usize| {
+            let (eye, dir) = (self.ray.eye + eye[k], self.ray.dir + dir[k]);
+            let t = (self.position - eye) * n / (dir * n);
+            t.is_finite().then(|| eye + t * dir - self.position)
+        };
+        Some([offset(0)?, offset(1)?])
+    }
+    // The change in `uv` per pixel in x and in y, solving dp = du dp/du +
+    // dv dp/dv in the least-squares sense.
+    pub fn uv_differentials(&self) -> Option<[[f64; 2]; 2]> {
+        let [dpdu, dpdv] = self.tangents?;
+        let (a, b, c) = (dpdu.sq(), dpdu * dpdv, dpdv.sq());
+        let det = a * c - b * b;
+        if det.abs() < 1e-24 {
+            return None;
         }
+        let [dpdx, dpdy] = self.position_differentials()?;
+        Some([dpdx, dpdy].map(|dp| {
+            let (pu, pv) = (dpdu * dp, dpdv * dp);
+            [(c * pu - b * pv) / det, (a * pv - b * pu) / det]
+        }))
+    }
+    // The mirror reflection of the incoming ray, carrying its differentials;
+    // the change in normal across the footprint is neglected.
+    pub fn reflected(&self) -> Ray {
+        let n = self.shading_normal();
+        let reflect = |v: Vector| v - 2. * (v * n) * n;
+        let ray = Ray::new(self.position, reflect(self.ray.dir)).with_time(self.ray.time);
+        match (self.ray.differentials, self.position_differentials()) {
+            (Some(Differentials { dir, .. }), Some(eye)) => ray.with_differentials(Differentials {
+                eye,
+                dir: dir.map(reflect),
+            }),
+            _ => ray,
+        }
+    }
+}
+
+impl Sphere {
+    fn roots(&self, ray: Ray) -> Option<[f64; 2]> {
+        poly::sphere(ray.eye - self.centre, ray.dir, self.radius)
+    }
+    fn hit(&self, ray: Ray, t: f64) -> HitRecord<'_> {
+        let normal = (ray.at(t) - self.centre) / self.radius;
+        HitRecord {
+            uv: Some(Self::uv(normal)),
+            tangents: self.tangents(normal),
+            ..HitRecord::new(self, ray, t, normal, self.material)
+        }
+    }
+    // Derivatives of the `uv` parametrisation, which are undefined at the poles.
+    pub fn tangents(&self, normal: Vector) -> Option<[Vector; 2]> {
+        let cos = normal.x.hypot(normal.z);
+        if cos < 1e-9 {
+            return None;
+        }
+        let r = self.radius;
+        Some([
+            std::f64::consts::TAU * r * Vector::new(-normal.z, 0., normal.x),
+            std::f64::consts::PI
+                * r
+                * Vector::new(-normal.y * normal.x / cos, cos, -normal.y * normal.z / cos),
+        ])
+    }
+    pub fn uv(normal: Vector) -> [f64; 2] {
+        [
+            0.5 + normal.z.atan2(normal.x) / std::f64::consts::TAU,
+            0.5 + normal.y.clamp(-1., 1.).asin() / std::f64::consts::PI,
+        ]
+    }
+}
+
+impl Prop for Sphere {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.roots(ray)?
+            .into_iter()
+            .find(|&t| t >= eps)
+            .map(|t| self.hit(ray, t))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.roots(ray)
+            .into_iter()
+            .flatten()
+            .filter(|&t| t >= eps)
+            .map(|t| self.hit(ray, t))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.centre, self.radius)
+    }
+}
 
-        let disp = ray.eye - self.centre;
-        let d4 = sq(disp * ray.dir) - disp.sq() * ray.dir.sq() + ray.dir.sq() * sq(self.radius);
-        if d4 >= 0. {
-            let t1 = (-disp * ray.dir - d4.sqrt()) / ray.dir.sq();
-            let t2 = (-disp * ray.dir + d4.sqrt()) / ray.dir.sq();
-            if t1 >= eps {
-                Some(HitRecord {
-                    prop: self,
-                    ray,
-                    distance: ray.distance(t1),
-                    position: ray.at(t1),
-                    normal: (ray.at(t1) - self.centre) / self.radius,
-                    material: self.material,
-                })
-            } else if t2 >= eps {
-                Some(HitRecord {
-                    prop: self,
-                    ray,
-                    distance: ray.distance(t2),
-                    position: ray.at(t2),
-                    normal: (ray.at(t2) - self.centre) / self.radius,
-                    material: self.material,
-                })
+impl<P: Prop + ?Sized> Prop for Box<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        (**self).raycast(ray, eps)
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        (**self).raycast_all(ray, eps)
+    }
+    fn bounds(&self) -> Aabb {
+        (**self).bounds()
+    }
+}
+
+impl<P: Prop + ?Sized> Prop for Arc<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        (**self).raycast(ray, eps)
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        (**self).raycast_all(ray, eps)
+    }
+    fn bounds(&self) -> Aabb {
+        (**self).bounds()
+    }
+}
+
+impl<P: Prop> Prop for Vec<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.iter()
+            .filter_map(|p| p.raycast(ray, eps))
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        let all = self
+            .iter()
+            .map(|p| p.raycast_all(ray, eps))
+            .collect::<Vec<_>>();
+
+        // the union of the members' intervals: only the crossings where the
+        // count of members the ray is inside goes to or from zero
+        let mut depth = all
+            .iter()
+            .filter(|hits| hits.first().is_some_and(|h| !h.entering()))
+            .count();
+        let mut hits = all.into_iter().flatten().collect::<Vec<_>>();
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits.retain(|hit| {
+            if hit.entering() {
+                depth += 1;
+                depth == 1
             } else {
-                None
+                depth = depth.saturating_sub(1);
+                depth == 0
             }
-        } else {
-            None
+        });
+        hits
+    }
+    fn bounds(&self) -> Aabb {
+        self.iter().map(P::bounds).fold(Aabb::EMPTY, Aabb::union)
+    }
+}
+
+impl<P: Prop> Transformed<P> {
+    pub const fn new(prop: P, transform: Transform) -> Self {
+        Self { prop, transform }
+    }
+    fn to_world<'a>(&self, ray: Ray, hit: HitRecord<'a>) -> HitRecord<'a> {
+        let t = hit.t();
+        HitRecord {
+            ray,
+            distance: ray.distance(t),
+            position: ray.at(t),
+            normal: self.transform.normal(hit.normal).norm(),
+            bumped: hit.bumped.map(|n| self.transform.normal(n).norm()),
+            tangents: hit
+                .tangents
+                .map(|tangents| tangents.map(|tangent| self.transform.vector(tangent))),
+            shading: match hit.shading {
+                Shading::Hair { tangent } => Shading::Hair {
+                    tangent: self.transform.vector(tangent).norm(),
+                },
+                shading => shading,
+            },
+            ..hit
         }
     }
 }
+
+impl Instance {
+    pub fn of(prop: &Arc<dyn Prop>, transform: Transform) -> Self {
+        Self::new(Arc::clone(prop), transform)
+    }
+}
+
+impl<P: Prop> Prop for Transformed<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let local = self.transform.inverse().ray(ray);
+        let hit = self.prop.raycast(local, eps)?;
+        Some(self.to_world(ray, hit))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        let local = self.transform.inverse().ray(ray);
+        self.prop
+            .raycast_all(local, eps)
+            .into_iter()
+            .map(|hit| self.to_world(ray, hit))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.transform.aabb(self.prop.bounds())
+    }
+}
+
+impl<P: Prop> Moving<P> {
+    pub fn offset_at(&self, time: f64) -> Vector {
+        let [t0, t1] = self.time;
+        let s = if t1 > t0 {
+            ((time - t0) / (t1 - t0)).clamp(0., 1.)
+        } else {
+            0.
+        };
+        self.offset[0] + s * (self.offset[1] - self.offset[0])
+    }
+}
+
+impl<P: Prop> Prop for Moving<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let offset = self.offset_at(ray.time);
+        let local = Ray {
+            eye: ray.eye - offset,
+            ..ray
+        };
+        self.prop.raycast(local, eps).map(|hit| HitRecord {
+            ray,
+            position: hit.position + offset,
+            ..hit
+        })
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        let offset = self.offset_at(ray.time);
+        let local = Ray {
+            eye: ray.eye - offset,
+            ..ray
+        };
+        self.prop
+            .raycast_all(local, eps)
+            .into_iter()
+            .map(|hit| HitRecord {
+                ray,
+                position: hit.position + offset,
+                ..hit
+            })
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        let bounds = self.prop.bounds();
+        bounds
+            .translate(self.offset[0])
+            .union(bounds.translate(self.offset[1]))
+    }
+}
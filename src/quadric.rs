@@ -0,0 +1,133 @@
+use crate::poly;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Quadric {
+    pub coeffs: [f64; 10],
+    pub clip: Option<Aabb>,
+    pub material: Material,
+}
+
+impl Quadric {
+    pub const fn new(coeffs: [f64; 10], material: Material) -> Self {
+        Self {
+            coeffs,
+            clip: None,
+            material,
+        }
+    }
+    fn axis_aligned(centre: Vector, [a, b, c, i, j]: [f64; 5], material: Material) -> Self {
+        Self::new([a, b, c, 0., 0., 0., 0., 0., i, j], material).translate(centre)
+    }
+    pub fn ellipsoid(centre: Vector, radii: Vector, material: Material) -> Self {
+        let Vector { x, y, z } = radii;
+        Self::axis_aligned(
+            centre,
+            [1. / (x * x), 1. / (y * y), 1. / (z * z), 0., -1.],
+            material,
+        )
+    }
+    pub fn paraboloid(vertex: Vector, radii: Vector, material: Material) -> Self {
+        let Vector { x, y, z } = radii;
+        Self::axis_aligned(
+            vertex,
+            [1. / (x * x), 1. / (y * y), 0., -1. / z, 0.],
+            material,
+        )
+    }
+    pub fn hyperboloid_one(centre: Vector, radii: Vector, material: Material) -> Self {
+        let Vector { x, y, z } = radii;
+        Self::axis_aligned(
+            centre,
+            [1. / (x * x), 1. / (y * y), -1. / (z * z), 0., -1.],
+            material,
+        )
+    }
+    pub fn hyperboloid_two(centre: Vector, radii: Vector, material: Material) -> Self {
+        let Vector { x, y, z } = radii;
+        Self::axis_aligned(
+            centre,
+            [1. / (x * x), 1. / (y * y), -1. / (z * z), 0., 1.],
+            material,
+        )
+    }
+    pub fn cone(apex: Vector, radii: Vector, material: Material) -> Self {
+        let Vector { x, y, z } = radii;
+        Self::axis_aligned(
+            apex,
+            [1. / (x * x), 1. / (y * y), -1. / (z * z), 0., 0.],
+            material,
+        )
+    }
+    pub const fn clipped(self, clip: Aabb) -> Self {
+        Self {
+            clip: Some(clip),
+            ..self
+        }
+    }
+    pub fn translate(self, offset: Vector) -> Self {
+        let [a, b, c, d, e, f, g, h, i, j] = self.coeffs;
+        let shift = 2. * self.quadratic_part(offset);
+        Self {
+            coeffs: [
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+                g - shift.x,
+                h - shift.y,
+                i - shift.z,
+                self.quadratic_form(offset) - Vector::new(g, h, i) * offset + j,
+            ],
+            clip: self.clip.map(|clip| clip.translate(offset)),
+            ..self
+        }
+    }
+    fn quadratic_part(&self, p: Vector) -> Vector {
+        let [a, b, c, d, e, f, ..] = self.coeffs;
+        Vector::new(
+            a * p.x + d / 2. * p.y + e / 2. * p.z,
+            d / 2. * p.x + b * p.y + f / 2. * p.z,
+            e / 2. * p.x + f / 2. * p.y + c * p.z,
+        )
+    }
+    fn quadratic_form(&self, p: Vector) -> f64 {
+        p * self.quadratic_part(p)
+    }
+    pub fn eval(&self, p: Vector) -> f64 {
+        let [.., g, h, i, j] = self.coeffs;
+        self.quadratic_form(p) + Vector::new(g, h, i) * p + j
+    }
+    pub fn gradient(&self, p: Vector) -> Vector {
+        let [.., g, h, i, _] = self.coeffs;
+        2. * self.quadratic_part(p) + Vector::new(g, h, i)
+    }
+    fn roots(&self, ray: Ray) -> Vec<f64> {
+        let [.., g, h, i, _] = self.coeffs;
+        poly::quadratic(
+            self.quadratic_form(ray.dir),
+            2. * (ray.eye * self.quadratic_part(ray.dir)) + Vector::new(g, h, i) * ray.dir,
+            self.eval(ray.eye),
+        )
+    }
+}
+
+impl Prop for Quadric {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.raycast_all(ray, eps).into_iter().next()
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.roots(ray)
+            .into_iter()
+            .filter(|&t| t >= eps)
+            .filter(|&t| self.clip.is_none_or(|clip| clip.contains(ray.at(t))))
+            .map(|t| HitRecord::new(self, ray, t, self.gradient(ray.at(t)).norm(), self.material))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.clip.unwrap_or(Aabb::INFINITE)
+    }
+}
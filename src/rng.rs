@@ -0,0 +1,407 @@
+use crate::vector::Vector;
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+
+const GOLDEN: u64 = 0x9e3779b97f4a7c15;
+
+pub const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+pub trait Rng {
+    fn next_u32(&mut self) -> u32;
+    fn next_u64(&mut self) -> u64 {
+        (self.next_u32() as u64) << 32 | self.next_u32() as u64
+    }
+    // Uniform in [0, 1), with all 53 bits of the mantissa random.
+    fn uniform(&mut self) -> f64 {
+        to_unit(self.next_u64())
+    }
+    fn uniform_2d(&mut self) -> [f64; 2] {
+        [self.uniform(), self.uniform()]
+    }
+    // Uniform in [0, n) without modulo bias, after Lemire; `n` must be
+    // positive.
+    fn below(&mut self, n: u32) -> u32 {
+        debug_assert!(n > 0, "below(0) has no values to return");
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let m = self.next_u32() as u64 * n as u64;
+            if m as u32 >= threshold {
+                return (m >> 32) as u32;
+            }
+        }
+    }
+}
+
+// O'Neill's PCG32 (XSH RR): 64 bits of state, with `stream` selecting one of
+// 2^63 independent sequences.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+// Blackman and Vigna's xoshiro256**.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Xoshiro256 {
+    s: [u64; 4],
+}
+
+// A stream of uniform numbers in [0, 1) that integrators consume one
+// dimension at a time.
+pub trait Uniform {
+    fn uniform(&mut self) -> f64;
+    fn uniform_2d(&mut self) -> [f64; 2] {
+        [self.uniform(), self.uniform()]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Sequence {
+    Random,
+    // jittered strata, shuffled independently in each dimension
+    Stratified,
+    // the Halton sequence, with its digits randomly scrambled per pixel
+    #[default]
+    Halton,
+}
+
+// The random numbers one sample of one pixel consumes, drawn dimension by
+// dimension from a sequence over all the pixel's samples.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Sampler {
+    rng: Pcg32,
+    sequence: Sequence,
+    index: usize,
+    count: usize,
+    dimension: usize,
+    scramble: u64,
+}
+
+// The splitmix64 finaliser, a strong 64-bit mixing function.
+pub const fn splitmix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+pub fn hash(seed: u64, values: impl IntoIterator<Item = u64>) -> u64 {
+    values
+        .into_iter()
+        .fold(splitmix(seed), |h, v| splitmix(h ^ v.wrapping_add(GOLDEN)))
+}
+
+// The top 53 bits as a float in [0, 1).
+pub fn to_unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl Pcg32 {
+    pub const fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: stream << 1 | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+    const fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(self.inc);
+    }
+}
+
+impl Rng for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+}
+
+impl Xoshiro256 {
+    pub const fn new(seed: u64) -> Self {
+        // expand the seed with splitmix64, as the authors recommend
+        let mut s = [0; 4];
+        let mut z = seed;
+        let mut i = 0;
+        while i < 4 {
+            z = z.wrapping_add(GOLDEN);
+            s[i] = splitmix(z);
+            i += 1;
+        }
+        Self { s }
+    }
+    // Advances 2^128 steps, giving non-overlapping streams for parallel use.
+    pub fn jump(&mut self) {
+        const JUMP: [u64; 4] = [
+            0x180ec6d33cfd0aba,
+            0xd5a61266f0c9392c,
+            0xa9582618e03fc9aa,
+            0x39abdc4529b1661c,
+        ];
+        let mut s = [0; 4];
+        for jump in JUMP {
+            for bit in 0..64 {
+                if jump & 1 << bit != 0 {
+                    for (s, x) in s.iter_mut().zip(self.s) {
+                        *s ^= x;
+                    }
+                }
+                self.next_u64();
+            }
+        }
+        self.s = s;
+    }
+}
+
+impl Rng for Xoshiro256 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+}
+
+impl Sampler {
+    // Seeded by the pixel and sample alone, so a render is the same however
+    // its pixels are divided between threads.
+    pub fn new(
+        sequence: Sequence,
+        seed: u64,
+        [x, y]: [usize; 2],
+        sample: usize,
+        count: usize,
+    ) -> Self {
+        let scramble = hash(seed, [x as u64, y as u64]);
+        Self {
+            rng: Pcg32::new(scramble, sample as u64),
+            sequence,
+            index: sample,
+            count: count.max(1),
+            dimension: 0,
+            scramble,
+        }
+    }
+    fn shift(&self, dimension: usize) -> u64 {
+        hash(self.scramble, [dimension as u64])
+    }
+    // This sample's stratum in `dimension`, under a permutation of its own so
+    // strata don't line up across dimensions.
+    fn stratum(&self, dimension: usize) -> usize {
+        let count = self.count.min(u32::MAX as usize) as u32;
+        let index = (self.index % count as usize) as u32;
+        permute(index, count, self.shift(dimension) as u32) as usize
+    }
+}
+
+impl Uniform for Sampler {
+    fn uniform(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        match self.sequence {
+            Sequence::Random => self.rng.uniform(),
+            Sequence::Stratified => {
+                (self.stratum(dimension) as f64 + self.rng.uniform()) / self.count as f64
+            }
+            Sequence::Halton if dimension < PRIMES.len() => scrambled_radical_inverse(
+                PRIMES[dimension],
+                self.index as u64,
+                self.shift(dimension),
+            ),
+            Sequence::Halton => self.rng.uniform(),
+        }
+    }
+    fn uniform_2d(&mut self) -> [f64; 2] {
+        if self.sequence != Sequence::Stratified {
+            return [self.uniform(), self.uniform()];
+        }
+        // a jittered grid, as square as the sample count allows
+        let dimension = self.dimension;
+        self.dimension += 2;
+        let columns = (self.count as f64).sqrt().ceil() as usize;
+        let rows = self.count.div_ceil(columns);
+        let stratum = self.stratum(dimension);
+        [
+            ((stratum % columns) as f64 + self.rng.uniform()) / columns as f64,
+            ((stratum / columns) as f64 + self.rng.uniform()) / rows as f64,
+        ]
+    }
+}
+
+// Element `index` of a pseudo-random permutation of 0..count chosen by
+// `seed`, without building it; Kensler's hash, cycle-walked back into range.
+// `index` must be below `count`, so the range can't be empty.
+pub fn permute(mut index: u32, count: u32, seed: u32) -> u32 {
+    debug_assert!(index < count, "index out of range for the permutation");
+    let mut mask = count.saturating_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dcb303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e501cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < count {
+            return (index.wrapping_add(seed)) % count;
+        }
+    }
+}
+
+// The digits of `index` in `base`, mirrored about the radix point.
+pub fn radical_inverse(base: u64, mut index: u64) -> f64 {
+    let inv = 1. / base as f64;
+    let (mut reversed, mut scale) = (0., 1.);
+    while index > 0 {
+        scale *= inv;
+        reversed += (index % base) as f64 * scale;
+        index /= base;
+    }
+    reversed.min(1. - f64::EPSILON / 2.)
+}
+
+// The radical inverse with each digit position put through its own random
+// permutation, which breaks up the correlated runs the Halton sequence has in
+// large bases. Past the index's last digit the permuted zeros are uniformly
+// random, so they are drawn in one go.
+pub fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64) -> f64 {
+    let inv = 1. / base as f64;
+    let (mut reversed, mut scale) = (0., 1.);
+    let mut position: u64 = 1;
+    while index > 0 {
+        // an affine map is a permutation of the digits as the base is prime
+        let h = splitmix(seed.wrapping_add(position * GOLDEN));
+        let (a, c) = (1 + h % (base - 1).max(1), (h >> 32) % base);
+        scale *= inv;
+        reversed += ((a * (index % base) + c) % base) as f64 * scale;
+        index /= base;
+        position += 1;
+    }
+    let tail = to_unit(splitmix(seed.wrapping_add(position * GOLDEN)));
+    (reversed + scale * tail).min(1. - f64::EPSILON / 2.)
+}
+
+pub fn halton(index: usize, dimension: usize) -> f64 {
+    radical_inverse(PRIMES[dimension], index as u64)
+}
+
+pub fn hammersley(index: usize, count: usize) -> [f64; 2] {
+    [
+        index as f64 / count as f64,
+        radical_inverse(2, index as u64),
+    ]
+}
+
+// A unit vector in spherical coordinates about the z axis.
+pub fn spherical(cos: f64, phi: f64) -> Vector {
+    let sin = (1. - cos * cos).max(0.).sqrt();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    Vector::new(sin * cos_phi, sin * sin_phi, cos)
+}
+
+// Carries a vector about the z axis to the same place about `axis`.
+pub fn frame(axis: Vector, local: Vector) -> Vector {
+    let axis = axis.norm();
+    let [t, b] = axis.orthonormal();
+    local.x * t + local.y * b + local.z * axis
+}
+
+// Shirley and Chiu's concentric map, which keeps strata compact.
+pub fn disk([u1, u2]: [f64; 2]) -> [f64; 2] {
+    let (a, b) = (2. * u1 - 1., 2. * u2 - 1.);
+    if a == 0. && b == 0. {
+        return [0., 0.];
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_2 - FRAC_PI_4 * (a / b))
+    };
+    [r * theta.cos(), r * theta.sin()]
+}
+
+// Uniform on the unit sphere, with pdf 1 / 4π.
+pub fn sphere([u1, u2]: [f64; 2]) -> Vector {
+    spherical(1. - 2. * u1, TAU * u2)
+}
+
+// Uniform over the hemisphere about z, with pdf 1 / 2π.
+pub fn hemisphere([u1, u2]: [f64; 2]) -> Vector {
+    spherical(u1, TAU * u2)
+}
+
+// Malley's method, with pdf cos θ / π.
+pub fn cosine_hemisphere(u: [f64; 2]) -> Vector {
+    let [x, y] = disk(u);
+    Vector::new(x, y, (1. - x * x - y * y).max(0.).sqrt())
+}
+
+// Uniform over the cone where 1 - cos θ < `spread`, with pdf 1 / 2π spread;
+// taking the spread rather than the angle keeps narrow cones precise.
+pub fn cone(spread: f64, [u1, u2]: [f64; 2]) -> Vector {
+    spherical(1. - u1 * spread, TAU * u2)
+}
+
+// Distributed as cos^n θ, with pdf (n + 1) cos^n θ / 2π.
+pub fn power_cosine(exponent: f64, [u1, u2]: [f64; 2]) -> Vector {
+    spherical(u1.powf(1. / (exponent + 1.)), TAU * u2)
+}
+
+// Uniform barycentric coordinates over a triangle.
+pub fn triangle([u1, u2]: [f64; 2]) -> [f64; 3] {
+    let s = u1.sqrt();
+    [1. - s, s * (1. - u2), s * u2]
+}
+
+pub const fn sphere_pdf() -> f64 {
+    1. / (4. * PI)
+}
+
+pub const fn hemisphere_pdf() -> f64 {
+    1. / TAU
+}
+
+pub fn cosine_hemisphere_pdf(cos: f64) -> f64 {
+    cos.max(0.) / PI
+}
+
+pub fn cone_pdf(spread: f64) -> f64 {
+    1. / (TAU * spread)
+}
+
+pub fn power_cosine_pdf(exponent: f64, cos: f64) -> f64 {
+    (exponent + 1.) / TAU * cos.max(0.).powf(exponent)
+}
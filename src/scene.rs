@@ -1,12 +1,21 @@
 use crate::image::Image;
-use crate::pixel::{Pixel, Rgb};
-use crate::prop::{HitRecord, Prop};
-use crate::vector::{Ray, Vector};
+use crate::integrator::{Integrator, Local};
+use crate::matrix::Matrix4;
+use crate::pixel::{Colour, Pixel, Rgb, Rgba};
+use crate::poly;
+use crate::prop::{HitRecord, Prop, Shading};
+use crate::rng::{self, Sampler, Sequence, Uniform};
+use crate::vector::{Differentials, Quaternion, Ray, Vector};
+use std::f64::consts::PI;
 
+// Fields are added as features land; build one with `Light::point` and set
+// the rest rather than writing a literal.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Light {
     pub position: Vector,
     pub colour: Rgb,
+    pub radius: f64,
+    pub intensity: f64,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -16,15 +25,149 @@ pub struct Camera {
     up: Vector,
     right: Vector,
     pub hfov: f64,
+    pub shutter: [f64; 2],
     unit_focus: f64,
 }
 
+pub trait Projection {
+    fn ray(&self, pixel: [f64; 2], dims: [usize; 2]) -> Ray;
+    fn shutter(&self) -> [f64; 2] {
+        [0., 0.]
+    }
+    fn project(&self, _point: Vector, _dims: [usize; 2]) -> Option<Projected> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projected {
+    pub pixel: [f64; 2],
+    pub eye: Vector,
+    pub pdf: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emission {
+    pub ray: Ray,
+    pub normal: Vector,
+    pub radiance: Colour,
+    pub pdf_position: f64,
+    pub pdf_dir: f64,
+}
+
+// As with `Light`, start from `Scene::new` rather than a literal.
 #[derive(Debug)]
 pub struct Scene {
     pub props: Vec<Box<dyn Prop>>,
     pub light: Light,
     pub camera: Camera,
+    pub samples: usize,
     pub eps: f64,
+    pub integrator: Box<dyn Integrator>,
+    pub sequence: Sequence,
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightSample {
+    pub dir: Vector,
+    pub distance: f64,
+    pub value: Colour,
+    pub pdf: f64,
+}
+
+impl Light {
+    pub const fn point(position: Vector, colour: Rgb) -> Self {
+        Self {
+            position,
+            colour,
+            radius: 0.,
+            intensity: 1.,
+        }
+    }
+    pub fn radiance(&self) -> Colour {
+        self.intensity / (PI * self.radius * self.radius) * Colour::from(self.colour)
+    }
+    fn cone(&self, p: Vector) -> Option<f64> {
+        let d2 = (self.position - p).sq();
+        let sin2 = self.radius * self.radius / d2;
+        (sin2 < 1.).then(|| sin2 / (1. + (1. - sin2).sqrt()))
+    }
+    pub fn sample(&self, p: Vector, [u1, u2]: [f64; 2]) -> Option<LightSample> {
+        let disp = self.position - p;
+        if self.radius <= 0. {
+            let d2 = disp.sq();
+            return Some(LightSample {
+                dir: disp.norm(),
+                distance: d2.sqrt(),
+                value: self.intensity / d2 * Colour::from(self.colour),
+                pdf: f64::INFINITY,
+            });
+        }
+        let spread = self.cone(p)?;
+        let dir = rng::frame(disp, rng::cone(spread, [u1, u2]));
+        let pdf = self.pdf(p);
+        let along = dir * disp;
+        let distance = along
+            - (self.radius * self.radius - (disp.sq() - along * along))
+                .max(0.)
+                .sqrt();
+        Some(LightSample {
+            dir,
+            distance,
+            value: self.radiance() / pdf,
+            pdf,
+        })
+    }
+    pub fn pdf(&self, p: Vector) -> f64 {
+        match self.cone(p) {
+            Some(spread) if self.radius > 0. => rng::cone_pdf(spread),
+            _ => 0.,
+        }
+    }
+    pub fn emit(&self, position: [f64; 2], [u1, u2]: [f64; 2]) -> Emission {
+        let colour = Colour::from(self.colour);
+        if self.radius <= 0. {
+            return Emission {
+                ray: Ray::new(self.position, rng::sphere([u1, u2])),
+                normal: Vector::default(),
+                radiance: self.intensity * colour,
+                pdf_position: 1.,
+                pdf_dir: rng::sphere_pdf(),
+            };
+        }
+        let normal = rng::sphere(position);
+        let dir = rng::frame(normal, rng::cosine_hemisphere([u1, u2]));
+        Emission {
+            ray: Ray::new(self.position + self.radius * normal, dir),
+            normal,
+            radiance: self.radiance(),
+            pdf_position: self.emit_pdf_position(),
+            pdf_dir: rng::cosine_hemisphere_pdf(normal * dir),
+        }
+    }
+    pub fn emit_pdf_position(&self) -> f64 {
+        if self.radius <= 0. {
+            0.
+        } else {
+            rng::sphere_pdf() / (self.radius * self.radius)
+        }
+    }
+    pub fn emit_pdf_dir(&self, normal: Vector, dir: Vector) -> f64 {
+        if self.radius <= 0. {
+            rng::sphere_pdf()
+        } else {
+            rng::cosine_hemisphere_pdf(normal * dir)
+        }
+    }
+    pub fn hit(&self, ray: Ray, eps: f64) -> Option<f64> {
+        if self.radius <= 0. {
+            return None;
+        }
+        poly::sphere(ray.eye - self.position, ray.dir, self.radius)?
+            .into_iter()
+            .find(|&t| t >= eps)
+    }
 }
 
 impl Scene {
@@ -33,7 +176,11 @@ impl Scene {
             props: Vec::new(),
             light,
             camera,
+            samples: 1,
             eps: 1e-6,
+            integrator: Box::new(Local),
+            sequence: Sequence::default(),
+            seed: 0,
         }
     }
     pub fn clear(&mut self) {
@@ -42,42 +189,69 @@ impl Scene {
     pub fn push(&mut self, prop: impl Prop) {
         self.props.push(Box::new(prop));
     }
-    pub fn raycast(&self, [x, y]: [usize; 2], [w, h]: [usize; 2]) -> Option<Rgb> {
-        let focus = self.camera.focus(w);
-        let xproj = (x as isize - w as isize / 2) as f64;
-        let yproj = -(y as isize - h as isize / 2) as f64;
-
-        let ray = Ray {
-            eye: self.camera.eye,
-            dir: focus * self.camera.centre()
-                + xproj * self.camera.right()
-                + yproj * self.camera.up(),
-        };
-
+    pub fn raycast(&self, [x, y]: [usize; 2], dims: [usize; 2]) -> Option<Rgb> {
+        let [open, close] = self.camera.shutter();
+        let mut sampler = self.sampler([x, y], 0);
+        let time = open + (close - open) * sampler.uniform();
+        let ray = self.camera.ray([x as f64, y as f64], dims).with_time(time);
+        self.integrator
+            .radiance(self, ray, &mut sampler)
+            .map(Colour::to_rgb)
+    }
+    pub fn sampler(&self, pixel: [usize; 2], sample: usize) -> Sampler {
+        Sampler::new(self.sequence, self.seed, pixel, sample, self.samples)
+    }
+    pub fn hit(&self, ray: Ray) -> Option<HitRecord<'_>> {
         self.props
             .iter()
             .filter_map(|p| p.raycast(ray, self.eps))
             .min_by(|a, b| a.distance.total_cmp(&b.distance))
-            .map(|h| self.shade(h))
+    }
+    pub fn trace(&self, ray: Ray) -> Option<Rgb> {
+        self.hit(ray).map(|h| self.shade(h))
+    }
+    pub fn occluded(&self, from: Vector, to: Vector, time: f64) -> bool {
+        let disp = to - from;
+        self.props.iter().any(|p| {
+            p.raycast(Ray::new(from, disp).with_time(time), self.eps)
+                .is_some_and(|h| h.distance - disp.abs() <= -self.eps)
+        })
     }
     pub fn shade(&self, hit: HitRecord) -> Rgb {
         let disp = self.light.position - hit.position;
-        let occluded = self.props.iter().any(|p| {
-            p.raycast(Ray::new(hit.position, disp), self.eps)
-                .is_some_and(|h| h.distance - disp.abs() <= -self.eps)
-        });
+        let occluded = self.occluded(hit.position, self.light.position, hit.ray.time);
 
         let ambient = hit.material.ambient * hit.material.colour * self.light.colour;
+        let ambient = match hit.shading {
+            Shading::Pbr(pbr) => ambient + pbr.emissive.to_rgb(),
+            _ => ambient,
+        };
 
         if occluded {
             ambient
         } else {
             let l = disp.norm();
-            let cos_d = l * hit.normal;
+            let v = -hit.ray.dir.norm();
+            let n = hit.shading_normal();
+            let (cos_d, cos_s) = match hit.shading {
+                Shading::Phong | Shading::Dielectric { .. } => {
+                    let cos_d = l * n;
+                    let r = 2. * cos_d * n - l;
+                    (cos_d, r * v)
+                }
+                Shading::Hair { tangent } => {
+                    let (cos_l, cos_v) = (tangent * l, tangent * v);
+                    let (sin_l, sin_v) = ((1. - cos_l * cos_l).sqrt(), (1. - cos_v * cos_v).sqrt());
+                    (sin_l, sin_l * sin_v - cos_l * cos_v)
+                }
+                Shading::Pbr(pbr) => {
+                    let brdf = pbr.eval(hit.material.colour.into(), n, v, l);
+                    let direct = PI * (l * n).max(0.) * brdf * Colour::from(self.light.colour);
+                    return ambient + direct.to_rgb();
+                }
+            };
             let diffuse = hit.material.diffuse * cos_d.max(0.) * hit.material.colour;
 
-            let r = 2. * cos_d * hit.normal - l;
-            let cos_s = r * -hit.ray.dir.norm();
             let specular = hit.material.specular
                 * cos_s.max(0.).powf(hit.material.shininess)
                 * self.light.colour;
@@ -87,26 +261,110 @@ impl Scene {
     }
     pub fn render<P: Pixel, const W: usize, const H: usize>(
         &self,
+        bg: impl FnMut([usize; 2]) -> P,
+    ) -> Image<P, W, H> {
+        self.render_through(&self.camera, bg)
+    }
+    pub fn render_through<P: Pixel, const W: usize, const H: usize>(
+        &self,
+        projection: &impl Projection,
         mut bg: impl FnMut([usize; 2]) -> P,
     ) -> Image<P, W, H> {
-        Image::fill_with(|c| {
-            self.raycast(c, [W, H])
-                .map(Pixel::from_rgb)
-                .unwrap_or_else(|| bg(c))
+        let [open, close] = projection.shutter();
+        let samples = self.samples.max(1);
+
+        if let Some(film) = self.integrator.render(self, projection, [W, H]) {
+            return Image::fill_with(|[x, y]| match film[y * W + x] {
+                Some(colour) => P::from_rgb(colour.to_rgb()),
+                None => bg([x, y]),
+            });
+        }
+
+        Image::fill_with(|[x, y]| {
+            let hits = (0..samples)
+                .map(|i| {
+                    let time = open + (close - open) * (i as f64 + 0.5) / samples as f64;
+                    let mut sampler = self.sampler([x, y], i);
+                    let [u, v] = sampler.uniform_2d();
+                    let [dx, dy] = if samples > 1 {
+                        [u - 0.5, v - 0.5]
+                    } else {
+                        [0.; 2]
+                    };
+                    let ray = projection.ray([x as f64 + dx, y as f64 + dy], [W, H]);
+                    self.integrator
+                        .radiance(self, ray.with_time(time), &mut sampler)
+                })
+                .collect::<Vec<_>>();
+
+            if hits.iter().all(Option::is_none) {
+                bg([x, y])
+            } else {
+                let bg = bg([x, y]).to_rgba();
+                let sum = hits.into_iter().fold([0.; 4], |acc, h| {
+                    let sample = match h {
+                        Some(Colour { r, g, b }) => [r, g, b].map(|c| c.clamp(0., 1.) * 255.),
+                        None => [bg.r, bg.g, bg.b].map(f64::from),
+                    };
+                    let a = if h.is_some() { 255. } else { f64::from(bg.a) };
+                    [
+                        acc[0] + sample[0],
+                        acc[1] + sample[1],
+                        acc[2] + sample[2],
+                        acc[3] + a,
+                    ]
+                });
+                let [r, g, b, a] = sum.map(|c| (c / samples as f64).round() as u8);
+                P::from_rgba(Rgba { r, g, b, a })
+            }
         })
     }
+    // Renders over `image`, which shows through wherever nothing is hit.
     pub fn render_on<P: Pixel, const W: usize, const H: usize>(
         &self,
-        mut image: Image<P, W, H>,
+        image: Image<P, W, H>,
     ) -> Image<P, W, H> {
-        for (y, row) in image.iter_mut().enumerate() {
-            for (x, pixel) in row.iter_mut().enumerate() {
-                if let Some(rgb) = self.raycast([x, y], [W, H]) {
-                    *pixel = Pixel::from_rgb(rgb);
-                }
-            }
+        self.render_through(&self.camera, |pixel| image[pixel])
+    }
+}
+
+impl Projection for Camera {
+    fn ray(&self, [x, y]: [f64; 2], [w, h]: [usize; 2]) -> Ray {
+        let xproj = x - (w / 2) as f64;
+        let yproj = (h / 2) as f64 - y;
+
+        Ray::new(
+            self.eye,
+            self.focus(w) * self.centre + xproj * self.right + yproj * self.up,
+        )
+        .with_differentials(Differentials {
+            eye: [Vector::default(); 2],
+            dir: [self.right, -self.up],
+        })
+    }
+    fn shutter(&self) -> [f64; 2] {
+        self.shutter
+    }
+    fn project(&self, point: Vector, [w, h]: [usize; 2]) -> Option<Projected> {
+        let disp = point - self.eye;
+        let z = disp * self.centre;
+        if z <= 0. {
+            return None;
+        }
+        let focus = self.focus(w);
+        let [x, y] = [
+            (w / 2) as f64 + focus * (disp * self.right) / z,
+            (h / 2) as f64 - focus * (disp * self.up) / z,
+        ];
+        if x < -0.5 || y < -0.5 || x >= w as f64 - 0.5 || y >= h as f64 - 0.5 {
+            return None;
         }
-        image
+        let cos = z / disp.abs();
+        Some(Projected {
+            pixel: [x, y],
+            eye: self.eye,
+            pdf: focus * focus / ((w * h) as f64 * cos * cos * cos),
+        })
     }
 }
 
@@ -123,6 +381,9 @@ impl Camera {
     pub const fn focus(&self, w: usize) -> f64 {
         w as f64 * self.unit_focus
     }
+    pub const fn moved_to(self, eye: Vector) -> Self {
+        Self { eye, ..self }
+    }
     pub fn new(eye: Vector, centre: Vector, up: Vector, hfov: f64) -> Self {
         let centre = centre.norm();
         let up = up.norm();
@@ -134,9 +395,26 @@ impl Camera {
             up,
             right,
             hfov,
+            shutter: [0., 0.],
             unit_focus: 0.5 / (hfov / 2.).to_radians().tan(),
         }
     }
+    pub fn oriented(eye: Vector, orientation: Quaternion, hfov: f64) -> Self {
+        Self::new(
+            eye,
+            orientation.rotate(Vector::K),
+            orientation.rotate(Vector::J),
+            hfov,
+        )
+    }
+    pub fn orientation(&self) -> Quaternion {
+        Quaternion::from_matrix(&Matrix4::from_basis(
+            self.right,
+            self.up,
+            self.centre,
+            Vector::default(),
+        ))
+    }
     pub fn px_towards_origin(dist: f64, hfov: f64) -> Self {
         const CENTRE: Vector = Vector::I;
         const UP: Vector = Vector::J;
@@ -147,6 +425,7 @@ impl Camera {
             centre: CENTRE,
             right: RIGHT,
             hfov,
+            shutter: [0., 0.],
             unit_focus: 0.5 / (hfov / 2.).to_radians().tan(),
         }
     }
@@ -160,6 +439,7 @@ impl Camera {
             up: UP,
             right: RIGHT,
             hfov,
+            shutter: [0., 0.],
             unit_focus: 0.5 / (hfov / 2.).to_radians().tan(),
         }
     }
@@ -173,6 +453,7 @@ impl Camera {
             up: UP,
             right: RIGHT,
             hfov,
+            shutter: [0., 0.],
             unit_focus: 0.5 / (hfov / 2.).to_radians().tan(),
         }
     }
@@ -186,6 +467,7 @@ impl Camera {
             up: UP,
             right: RIGHT,
             hfov,
+            shutter: [0., 0.],
             unit_focus: 0.5 / (hfov / 2.).to_radians().tan(),
         }
     }
@@ -199,6 +481,7 @@ impl Camera {
             up: UP,
             right: RIGHT,
             hfov,
+            shutter: [0., 0.],
             unit_focus: 0.5 / (hfov / 2.).to_radians().tan(),
         }
     }
@@ -212,6 +495,7 @@ impl Camera {
             up: UP,
             right: RIGHT,
             hfov,
+            shutter: [0., 0.],
             unit_focus: 0.5 / (hfov / 2.).to_radians().tan(),
         }
     }
@@ -0,0 +1,348 @@
+use crate::csg::Operation;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+
+pub trait Sdf: 'static + std::fmt::Debug {
+    fn distance(&self, p: Vector) -> f64;
+    fn gradient(&self, p: Vector, h: f64) -> Vector {
+        const K: [Vector; 4] = [
+            Vector::new(1., -1., -1.),
+            Vector::new(-1., -1., 1.),
+            Vector::new(-1., 1., -1.),
+            Vector::new(1., 1., 1.),
+        ];
+        K.iter()
+            .fold(Vector::default(), |acc, &k| {
+                acc + self.distance(p + h * k) * k
+            })
+            .norm()
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::INFINITE
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Sphere {
+    pub radius: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Cuboid {
+    pub half: Vector,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RoundedCuboid {
+    pub half: Vector,
+    pub radius: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Torus {
+    pub major: f64,
+    pub minor: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Capsule {
+    pub start: Vector,
+    pub end: Vector,
+    pub radius: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Translated<S: Sdf> {
+    pub sdf: S,
+    pub offset: Vector,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Blend<A: Sdf, B: Sdf> {
+    pub op: Operation,
+    pub left: A,
+    pub right: B,
+    pub k: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Repeat<S: Sdf> {
+    pub sdf: S,
+    pub period: Vector,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Twist<S: Sdf> {
+    pub sdf: S,
+    pub rate: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Bend<S: Sdf> {
+    pub sdf: S,
+    pub rate: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SdfProp<S: Sdf> {
+    pub sdf: S,
+    pub material: Material,
+    pub steps: usize,
+    pub max_distance: f64,
+    pub step: f64,
+    pub eps: f64,
+}
+
+impl Sdf for Sphere {
+    fn distance(&self, p: Vector) -> f64 {
+        p.abs() - self.radius
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::around(Vector::default(), self.radius)
+    }
+}
+
+impl Sdf for Cuboid {
+    fn distance(&self, p: Vector) -> f64 {
+        let q = Vector::new(p.x.abs(), p.y.abs(), p.z.abs()) - self.half;
+        let outside = Vector::new(q.x.max(0.), q.y.max(0.), q.z.max(0.)).abs();
+        outside + q.x.max(q.y).max(q.z).min(0.)
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::new(-self.half, self.half)
+    }
+}
+
+impl Sdf for RoundedCuboid {
+    fn distance(&self, p: Vector) -> f64 {
+        let r = Vector::new(self.radius, self.radius, self.radius);
+        Cuboid {
+            half: self.half - r,
+        }
+        .distance(p)
+            - self.radius
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::new(-self.half, self.half)
+    }
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: Vector) -> f64 {
+        let ring = (p.x * p.x + p.z * p.z).sqrt() - self.major;
+        (ring * ring + p.y * p.y).sqrt() - self.minor
+    }
+    fn bounds(&self) -> Aabb {
+        let outer = self.major + self.minor;
+        Aabb::new(
+            Vector::new(-outer, -self.minor, -outer),
+            Vector::new(outer, self.minor, outer),
+        )
+    }
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: Vector) -> f64 {
+        let axis = self.end - self.start;
+        let s = ((p - self.start) * axis / axis.sq()).clamp(0., 1.);
+        (p - self.start - s * axis).abs() - self.radius
+    }
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.start, self.radius).union(Aabb::around(self.end, self.radius))
+    }
+}
+
+impl<S: Sdf> Sdf for Translated<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        self.sdf.distance(p - self.offset)
+    }
+    fn bounds(&self) -> Aabb {
+        self.sdf.bounds().translate(self.offset)
+    }
+}
+
+impl<A: Sdf, B: Sdf> Blend<A, B> {
+    pub const fn union(left: A, right: B, k: f64) -> Self {
+        Self {
+            op: Operation::Union,
+            left,
+            right,
+            k,
+        }
+    }
+    pub const fn intersection(left: A, right: B, k: f64) -> Self {
+        Self {
+            op: Operation::Intersection,
+            left,
+            right,
+            k,
+        }
+    }
+    pub const fn subtraction(left: A, right: B, k: f64) -> Self {
+        Self {
+            op: Operation::Difference,
+            left,
+            right,
+            k,
+        }
+    }
+}
+
+impl<A: Sdf, B: Sdf> Sdf for Blend<A, B> {
+    fn distance(&self, p: Vector) -> f64 {
+        #[inline]
+        fn smin(a: f64, b: f64, k: f64) -> f64 {
+            if k <= 0. {
+                a.min(b)
+            } else {
+                let h = (0.5 + 0.5 * (b - a) / k).clamp(0., 1.);
+                b + h * (a - b) - k * h * (1. - h)
+            }
+        }
+
+        let a = self.left.distance(p);
+        let b = self.right.distance(p);
+        match self.op {
+            Operation::Union => smin(a, b, self.k),
+            Operation::Intersection => -smin(-a, -b, self.k),
+            Operation::Difference => -smin(-a, b, self.k),
+        }
+    }
+    fn bounds(&self) -> Aabb {
+        let pad = Vector::new(self.k, self.k, self.k);
+        let bounds = match self.op {
+            Operation::Union => self.left.bounds().union(self.right.bounds()),
+            Operation::Intersection => self.left.bounds().intersection(self.right.bounds()),
+            Operation::Difference => self.left.bounds(),
+        };
+        Aabb::new(bounds.min - pad, bounds.max + pad)
+    }
+}
+
+impl<S: Sdf> Sdf for Repeat<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        #[inline]
+        fn wrap(x: f64, period: f64) -> f64 {
+            if period > 0. {
+                x - period * (x / period).round()
+            } else {
+                x
+            }
+        }
+
+        self.sdf.distance(Vector::new(
+            wrap(p.x, self.period.x),
+            wrap(p.y, self.period.y),
+            wrap(p.z, self.period.z),
+        ))
+    }
+}
+
+impl<S: Sdf> Sdf for Twist<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        let angle = -(self.rate * p.y).to_degrees();
+        self.sdf.distance(p.rotate_on_axis(Vector::Y, angle))
+    }
+    fn bounds(&self) -> Aabb {
+        let b = self.sdf.bounds();
+        let x = b.min.x.abs().max(b.max.x.abs());
+        let z = b.min.z.abs().max(b.max.z.abs());
+        let r = x.hypot(z);
+        Aabb::new(Vector::new(-r, b.min.y, -r), Vector::new(r, b.max.y, r))
+    }
+}
+
+impl<S: Sdf> Sdf for Bend<S> {
+    fn distance(&self, p: Vector) -> f64 {
+        let angle = -(self.rate * p.x).to_degrees();
+        self.sdf.distance(p.rotate_on_axis(Vector::Z, angle))
+    }
+}
+
+impl<S: Sdf> SdfProp<S> {
+    pub const fn new(sdf: S, material: Material) -> Self {
+        Self {
+            sdf,
+            material,
+            steps: 256,
+            max_distance: 1e3,
+            step: 1.,
+            eps: 1e-4,
+        }
+    }
+    pub fn march(&self, ray: Ray, eps: f64) -> Option<f64> {
+        let len = ray.dir.abs();
+        let dir = ray.dir / len;
+        // `eps` bounds the parameter of the caller's ray, not the distance
+        let [start, end] = self.sdf.bounds().hit(Ray { dir, ..ray }, eps * len)?;
+        let end = end.min(self.max_distance);
+
+        let mut t = start.max(2. * self.eps);
+        for _ in 0..self.steps {
+            if t > end {
+                return None;
+            }
+            let d = self.sdf.distance(ray.eye + t * dir).abs();
+            if d < self.eps {
+                return Some(t / len);
+            }
+            t += self.step * d;
+        }
+        None
+    }
+    // Every crossing of the surface, marching on through the inside after
+    // each one; the sign of the distance says which side the ray is on.
+    pub fn march_all(&self, ray: Ray, eps: f64) -> Vec<f64> {
+        let len = ray.dir.abs();
+        let dir = ray.dir / len;
+        let Some([start, end]) = self.sdf.bounds().hit(Ray { dir, ..ray }, eps * len) else {
+            return Vec::new();
+        };
+        let end = end.min(self.max_distance);
+
+        let mut crossings = Vec::new();
+        let mut t = start.max(2. * self.eps);
+        let mut inside = self.sdf.distance(ray.eye + t * dir) < 0.;
+        let mut leaving = false;
+        for _ in 0..self.steps {
+            if t > end {
+                break;
+            }
+            let d = self.sdf.distance(ray.eye + t * dir);
+            if leaving && d.abs() < self.eps {
+                t += self.eps;
+                continue;
+            }
+            leaving = false;
+            if d.abs() < self.eps || (d < 0.) != inside {
+                crossings.push(t / len);
+                inside = !inside;
+                leaving = true;
+                t += 2. * self.eps;
+                continue;
+            }
+            t += self.step * d.abs();
+        }
+        crossings
+    }
+    fn hit(&self, ray: Ray, t: f64) -> HitRecord<'_> {
+        let normal = self.sdf.gradient(ray.at(t), self.eps);
+        HitRecord::new(self, ray, t, normal, self.material)
+    }
+}
+
+impl<S: Sdf> Prop for SdfProp<S> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let t = self.march(ray, eps)?;
+        Some(self.hit(ray, t))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.march_all(ray, eps)
+            .into_iter()
+            .map(|t| self.hit(ray, t))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.sdf.bounds()
+    }
+}
@@ -0,0 +1,219 @@
+use crate::image::Image;
+use crate::pixel::{Pixel, Rgb};
+use crate::scene::{Camera, Projection, Scene};
+use crate::vector::{Ray, Vector};
+use std::f64::consts::{PI, TAU};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Convergence {
+    Parallel,
+    ToeIn(f64),
+    OffAxis(f64),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Anaglyph {
+    Colour,
+    Half,
+    Grey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StereoRig {
+    pub camera: Camera,
+    pub interocular: f64,
+    pub convergence: Convergence,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StereoCamera {
+    pub camera: Camera,
+    pub shift: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OmniStereo {
+    pub centre: Vector,
+    pub forward: Vector,
+    pub up: Vector,
+    pub right: Vector,
+    pub offset: f64,
+    pub shutter: [f64; 2],
+}
+
+impl Eye {
+    pub const fn sign(self) -> f64 {
+        match self {
+            Eye::Left => -1.,
+            Eye::Right => 1.,
+        }
+    }
+}
+
+impl StereoRig {
+    pub const fn new(camera: Camera, interocular: f64, convergence: Convergence) -> Self {
+        Self {
+            camera,
+            interocular,
+            convergence,
+        }
+    }
+    pub fn eye(&self, eye: Eye) -> StereoCamera {
+        let offset = eye.sign() * self.interocular / 2.;
+        let position = self.camera.eye + offset * self.camera.right();
+
+        match self.convergence {
+            Convergence::Parallel => StereoCamera {
+                camera: self.camera.moved_to(position),
+                shift: 0.,
+            },
+            Convergence::ToeIn(dist) => {
+                let mut camera = Camera::new(
+                    position,
+                    self.camera.eye + dist * self.camera.centre() - position,
+                    self.camera.up(),
+                    self.camera.hfov,
+                );
+                camera.shutter = self.camera.shutter;
+                StereoCamera { camera, shift: 0. }
+            }
+            Convergence::OffAxis(dist) => StereoCamera {
+                camera: self.camera.moved_to(position),
+                shift: -offset / dist,
+            },
+        }
+    }
+    pub fn omni(&self, eye: Eye) -> OmniStereo {
+        OmniStereo {
+            centre: self.camera.eye,
+            forward: self.camera.centre(),
+            up: self.camera.up(),
+            right: self.camera.right(),
+            offset: eye.sign() * self.interocular / 2.,
+            shutter: self.camera.shutter,
+        }
+    }
+    pub fn render<P: Pixel, const W: usize, const H: usize>(
+        &self,
+        scene: &Scene,
+        eye: Eye,
+        bg: impl FnMut([usize; 2]) -> P,
+    ) -> Image<P, W, H> {
+        scene.render_through(&self.eye(eye), bg)
+    }
+    pub fn render_pair<P: Pixel, const W: usize, const H: usize>(
+        &self,
+        scene: &Scene,
+        mut bg: impl FnMut([usize; 2]) -> P,
+    ) -> [Image<P, W, H>; 2] {
+        [
+            self.render(scene, Eye::Left, &mut bg),
+            self.render(scene, Eye::Right, &mut bg),
+        ]
+    }
+    pub fn render_omni_pair<P: Pixel, const W: usize, const H: usize>(
+        &self,
+        scene: &Scene,
+        mut bg: impl FnMut([usize; 2]) -> P,
+    ) -> [Image<P, W, H>; 2] {
+        [
+            scene.render_through(&self.omni(Eye::Left), &mut bg),
+            scene.render_through(&self.omni(Eye::Right), &mut bg),
+        ]
+    }
+}
+
+impl Projection for StereoCamera {
+    fn ray(&self, pixel: [f64; 2], dims: [usize; 2]) -> Ray {
+        let ray = self.camera.ray(pixel, dims);
+        Ray {
+            dir: ray.dir + self.shift * self.camera.focus(dims[0]) * self.camera.right(),
+            ..ray
+        }
+    }
+    fn shutter(&self) -> [f64; 2] {
+        self.camera.shutter
+    }
+}
+
+impl Projection for OmniStereo {
+    fn ray(&self, [x, y]: [f64; 2], [w, h]: [usize; 2]) -> Ray {
+        let theta = (x + 0.5) / w as f64 * TAU - PI;
+        let phi = PI / 2. - (y + 0.5) / h as f64 * PI;
+        let (sin_t, cos_t) = theta.sin_cos();
+        let (sin_p, cos_p) = phi.sin_cos();
+
+        let horizontal = cos_t * self.forward + sin_t * self.right;
+        let tangent = cos_t * self.right - sin_t * self.forward;
+
+        Ray::new(
+            self.centre + self.offset * tangent,
+            cos_p * horizontal + sin_p * self.up,
+        )
+    }
+    fn shutter(&self) -> [f64; 2] {
+        self.shutter
+    }
+}
+
+pub fn side_by_side<P: Pixel, const W: usize, const H: usize, const W2: usize>(
+    left: &Image<P, W, H>,
+    right: &Image<P, W, H>,
+) -> Image<P, W2, H> {
+    const { assert!(W2 == 2 * W) };
+    Image::fill_with(|[x, y]| {
+        if x < W {
+            left[[x, y]]
+        } else {
+            right[[x - W, y]]
+        }
+    })
+}
+
+pub fn over_under<P: Pixel, const W: usize, const H: usize, const H2: usize>(
+    left: &Image<P, W, H>,
+    right: &Image<P, W, H>,
+) -> Image<P, W, H2> {
+    const { assert!(H2 == 2 * H) };
+    Image::fill_with(|[x, y]| {
+        if y < H {
+            left[[x, y]]
+        } else {
+            right[[x, y - H]]
+        }
+    })
+}
+
+pub fn anaglyph<P: Pixel, const W: usize, const H: usize>(
+    left: &Image<P, W, H>,
+    right: &Image<P, W, H>,
+    mode: Anaglyph,
+) -> Image<P, W, H> {
+    Image::fill_with(|c| {
+        let l = left[c].to_rgb();
+        let r = right[c].to_rgb();
+        P::from_rgb(match mode {
+            Anaglyph::Colour => Rgb {
+                r: l.r,
+                g: r.g,
+                b: r.b,
+            },
+            Anaglyph::Half => Rgb {
+                r: l.to_grey(),
+                g: r.g,
+                b: r.b,
+            },
+            Anaglyph::Grey => Rgb {
+                r: l.to_grey(),
+                g: r.to_grey(),
+                b: r.to_grey(),
+            },
+        })
+    })
+}
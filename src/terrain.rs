@@ -0,0 +1,282 @@
+use crate::image::Image;
+use crate::pixel::Pixel;
+use crate::poly;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+use std::io::{Error, ErrorKind, Result};
+
+#[derive(Clone, Debug)]
+pub struct Heightfield {
+    heights: Vec<f64>,
+    size: [usize; 2],
+    levels: Vec<([usize; 2], Vec<[f64; 2]>)>,
+    pub origin: Vector,
+    pub scale: Vector,
+    pub material: Material,
+}
+
+impl Heightfield {
+    pub fn new(
+        heights: Vec<f64>,
+        [w, d]: [usize; 2],
+        origin: Vector,
+        scale: Vector,
+        material: Material,
+    ) -> Self {
+        assert!(w >= 2 && d >= 2, "heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), w * d, "heightfield size mismatch");
+
+        let mut cells = [w - 1, d - 1];
+        let mut minmax = Vec::with_capacity(cells[0] * cells[1]);
+        for j in 0..cells[1] {
+            for i in 0..cells[0] {
+                let corners = [
+                    heights[j * w + i],
+                    heights[j * w + i + 1],
+                    heights[(j + 1) * w + i],
+                    heights[(j + 1) * w + i + 1],
+                ];
+                minmax.push([
+                    corners.into_iter().fold(f64::INFINITY, f64::min),
+                    corners.into_iter().fold(f64::NEG_INFINITY, f64::max),
+                ]);
+            }
+        }
+
+        let mut levels = vec![(cells, minmax)];
+        while cells[0] > 1 || cells[1] > 1 {
+            let (prev, below) = levels.last().unwrap();
+            cells = [cells[0].div_ceil(2), cells[1].div_ceil(2)];
+            let mut minmax = vec![[f64::INFINITY, f64::NEG_INFINITY]; cells[0] * cells[1]];
+            for j in 0..prev[1] {
+                for i in 0..prev[0] {
+                    let [lo, hi] = below[j * prev[0] + i];
+                    let node = &mut minmax[j / 2 * cells[0] + i / 2];
+                    node[0] = node[0].min(lo);
+                    node[1] = node[1].max(hi);
+                }
+            }
+            levels.push((cells, minmax));
+        }
+
+        Self {
+            heights,
+            size: [w, d],
+            levels,
+            origin,
+            scale,
+            material,
+        }
+    }
+    pub fn from_image<P: Pixel, const W: usize, const H: usize>(
+        image: &Image<P, W, H>,
+        origin: Vector,
+        scale: Vector,
+        material: Material,
+    ) -> Self {
+        let heights = image
+            .iter()
+            .flat_map(|row| row.iter().map(|px| px.to_grey() as f64 / 255.))
+            .collect();
+        Self::new(heights, [W, H], origin, scale, material)
+    }
+    pub fn from_pgm(buf: &[u8], origin: Vector, scale: Vector, material: Material) -> Result<Self> {
+        fn invalid(msg: &str) -> Error {
+            Error::new(ErrorKind::InvalidData, format!("invalid PGM: {msg}"))
+        }
+
+        let mut pos = 0;
+        let mut token = || -> Result<&[u8]> {
+            loop {
+                while pos < buf.len() && buf[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                if pos < buf.len() && buf[pos] == b'#' {
+                    while pos < buf.len() && buf[pos] != b'\n' {
+                        pos += 1;
+                    }
+                } else {
+                    break;
+                }
+            }
+            let start = pos;
+            while pos < buf.len() && !buf[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                Err(invalid("unexpected end of file"))
+            } else {
+                Ok(&buf[start..pos])
+            }
+        };
+        fn number(token: &[u8]) -> Result<usize> {
+            std::str::from_utf8(token)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| invalid("expected a number"))
+        }
+
+        let magic = token()?.to_vec();
+        let w = number(token()?)?;
+        let d = number(token()?)?;
+        let maxval = number(token()?)?;
+        if maxval == 0 || maxval > 0xffff {
+            return Err(invalid("maxval out of range"));
+        }
+
+        if w < 2 || d < 2 {
+            return Err(invalid("heightfield needs at least 2x2 samples"));
+        }
+        let count = w
+            .checked_mul(d)
+            .ok_or_else(|| invalid("raster too large"))?;
+
+        let heights = match magic.as_slice() {
+            b"P2" => (0..count)
+                .map(|_| Ok(number(token()?)? as f64 / maxval as f64))
+                .collect::<Result<Vec<_>>>()?,
+            b"P5" => {
+                let data = buf
+                    .get(pos + 1..)
+                    .ok_or_else(|| invalid("truncated raster"))?;
+                let depth = if maxval > 0xff { 2 } else { 1 };
+                let bytes = count
+                    .checked_mul(depth)
+                    .ok_or_else(|| invalid("raster too large"))?;
+                if data.len() < bytes {
+                    return Err(invalid("truncated raster"));
+                }
+                data.chunks_exact(depth)
+                    .take(count)
+                    .map(|c| {
+                        c.iter().fold(0, |acc, &b| acc << 8 | b as usize) as f64 / maxval as f64
+                    })
+                    .collect()
+            }
+            _ => return Err(invalid("unsupported magic number")),
+        };
+        Ok(Self::new(heights, [w, d], origin, scale, material))
+    }
+    pub fn height(&self, [i, j]: [usize; 2]) -> f64 {
+        self.heights[j * self.size[0] + i]
+    }
+    fn slope(&self, [i, j]: [usize; 2]) -> [f64; 2] {
+        let [w, d] = self.size;
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(w - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(d - 1));
+        [
+            (self.height([i1, j]) - self.height([i0, j])) / (i1 - i0) as f64,
+            (self.height([i, j1]) - self.height([i, j0])) / (j1 - j0) as f64,
+        ]
+    }
+    fn to_grid(&self, ray: Ray) -> Ray {
+        let inv =
+            |v: Vector| Vector::new(v.x / self.scale.x, v.y / self.scale.y, v.z / self.scale.z);
+        Ray {
+            eye: inv(ray.eye - self.origin),
+            dir: inv(ray.dir),
+            ..ray
+        }
+    }
+    fn cell(&self, ray: Ray, [i, j]: [usize; 2], [t0, t1]: [f64; 2]) -> Option<(f64, Vector)> {
+        let h00 = self.height([i, j]);
+        let h10 = self.height([i + 1, j]);
+        let h01 = self.height([i, j + 1]);
+        let h11 = self.height([i + 1, j + 1]);
+        let (a, b, c, e) = (h00, h10 - h00, h01 - h00, h00 - h10 - h01 + h11);
+
+        let (u0, v0) = (ray.eye.x - i as f64, ray.eye.z - j as f64);
+        let (du, dv) = (ray.dir.x, ray.dir.z);
+
+        let t = poly::quadratic(
+            -e * du * dv,
+            ray.dir.y - (b * du + c * dv + e * (u0 * dv + v0 * du)),
+            ray.eye.y - (a + b * u0 + c * v0 + e * u0 * v0),
+        )
+        .into_iter()
+        .find(|&t| t0 <= t && t <= t1)?;
+
+        let (u, v) = ((u0 + t * du).clamp(0., 1.), (v0 + t * dv).clamp(0., 1.));
+        let corners = [[i, j], [i + 1, j], [i, j + 1], [i + 1, j + 1]].map(|c| self.slope(c));
+        let weights = [(1. - u) * (1. - v), u * (1. - v), (1. - u) * v, u * v];
+        let [gx, gz] = corners
+            .iter()
+            .zip(weights)
+            .fold([0., 0.], |[x, z], ([sx, sz], w)| [x + w * sx, z + w * sz]);
+        let normal = Vector::new(
+            -gx * self.scale.y / self.scale.x,
+            1.,
+            -gz * self.scale.y / self.scale.z,
+        );
+        Some((t, normal.norm()))
+    }
+    fn node(&self, level: usize, [i, j]: [usize; 2]) -> Aabb {
+        let (size, nodes) = &self.levels[level];
+        let [lo, hi] = nodes[j * size[0] + i];
+        let span = 1 << level;
+        let [cw, cd] = self.levels[0].0;
+        Aabb::new(
+            Vector::new((i * span) as f64, lo, (j * span) as f64),
+            Vector::new(
+                ((i + 1) * span).min(cw) as f64,
+                hi,
+                ((j + 1) * span).min(cd) as f64,
+            ),
+        )
+    }
+    fn visit(
+        &self,
+        ray: Ray,
+        eps: f64,
+        level: usize,
+        [i, j]: [usize; 2],
+        range: [f64; 2],
+    ) -> Option<(f64, Vector)> {
+        if level == 0 {
+            return self.cell(ray, [i, j], range);
+        }
+
+        let [cw, cd] = self.levels[level - 1].0;
+        let mut children = [[0, 0], [1, 0], [0, 1], [1, 1]]
+            .into_iter()
+            .map(|[di, dj]| [2 * i + di, 2 * j + dj])
+            .filter(|&[ci, cj]| ci < cw && cj < cd)
+            .filter_map(|c| Some((c, self.node(level - 1, c).hit(ray, eps)?)))
+            .collect::<Vec<_>>();
+        children.sort_by(|a, b| a.1[0].total_cmp(&b.1[0]));
+
+        let mut best: Option<(f64, Vector)> = None;
+        for (child, range) in children {
+            if best.is_some_and(|(t, _)| t < range[0]) {
+                break;
+            }
+            if let Some(hit) = self.visit(ray, eps, level - 1, child, range)
+                && best.is_none_or(|(t, _)| hit.0 < t)
+            {
+                best = Some(hit);
+            }
+        }
+        best
+    }
+}
+
+impl Prop for Heightfield {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let grid = self.to_grid(ray);
+        let top = self.levels.len() - 1;
+        let range = self.node(top, [0, 0]).hit(grid, eps)?;
+        let (t, normal) = self.visit(grid, eps, top, [0, 0], range)?;
+        Some(HitRecord::new(self, ray, t, normal, self.material))
+    }
+    fn bounds(&self) -> Aabb {
+        let [lo, hi] = self.levels.last().unwrap().1[0];
+        let [w, d] = self.size;
+        let corner = |x: f64, y: f64, z: f64| {
+            self.origin + Vector::new(x * self.scale.x, y * self.scale.y, z * self.scale.z)
+        };
+        Aabb::new(
+            corner(0., lo, 0.),
+            corner((w - 1) as f64, hi, (d - 1) as f64),
+        )
+    }
+}
@@ -0,0 +1,513 @@
+use crate::image::{Downsample, Image, Mip};
+use crate::pixel::{Colour, Pixel};
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+use std::ops::{Add, Mul};
+use std::sync::Arc;
+
+pub trait Texel:
+    'static + Copy + std::fmt::Debug + Add<Output = Self> + Mul<f64, Output = Self>
+{
+    fn from_pixel<P: Pixel>(pixel: P) -> Self;
+}
+
+pub trait Texture<T>: 'static + std::fmt::Debug {
+    fn sample(&self, hit: &HitRecord) -> T;
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Mapping {
+    #[default]
+    Uv,
+    Object,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+    Bicubic,
+    Trilinear,
+    Ewa,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Address {
+    #[default]
+    Wrap,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Solid<T: Texel> {
+    pub value: T,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Checker<T: Texel> {
+    pub values: [T; 2],
+    pub scale: f64,
+    pub mapping: Mapping,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stripes<T: Texel> {
+    pub values: [T; 2],
+    pub scale: f64,
+    pub mapping: Mapping,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient<T: Texel> {
+    stops: Vec<(f64, T)>,
+    pub mapping: Mapping,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageTexture<P: Pixel, const W: usize, const H: usize> {
+    pub image: Arc<Image<P, W, H>>,
+    pub mips: Arc<[Mip<P>]>,
+    pub filter: Filter,
+    pub address: [Address; 2],
+}
+
+#[derive(Clone, Debug)]
+pub struct Textured<P: Prop> {
+    pub prop: P,
+    pub colour: Option<Arc<dyn Texture<Colour>>>,
+    pub ambient: Option<Arc<dyn Texture<f64>>>,
+    pub diffuse: Option<Arc<dyn Texture<f64>>>,
+    pub specular: Option<Arc<dyn Texture<f64>>>,
+    pub shininess: Option<Arc<dyn Texture<f64>>>,
+    pub normal: Option<Arc<dyn Texture<Colour>>>,
+    pub bump: Option<(Arc<dyn Texture<f64>>, f64)>,
+}
+
+impl Texel for f64 {
+    fn from_pixel<P: Pixel>(pixel: P) -> Self {
+        pixel.to_grey() as f64 / 255.
+    }
+}
+
+impl Texel for Colour {
+    fn from_pixel<P: Pixel>(pixel: P) -> Self {
+        Self::from_rgb(pixel.to_rgb())
+    }
+}
+
+impl Mapping {
+    pub fn point(self, hit: &HitRecord) -> Vector {
+        match self {
+            Self::Uv => {
+                let [u, v] = hit.uv.unwrap_or_default();
+                Vector::new(u, v, 0.)
+            }
+            Self::Object => hit.position,
+        }
+    }
+}
+
+impl Address {
+    pub fn resolve(self, i: isize, n: usize) -> usize {
+        let n = n as isize;
+        (match self {
+            Self::Wrap => i.rem_euclid(n),
+            Self::Clamp => i.clamp(0, n - 1),
+            Self::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n { m } else { 2 * n - 1 - m }
+            }
+        }) as usize
+    }
+}
+
+impl<T: Texel> Texture<T> for Solid<T> {
+    fn sample(&self, _: &HitRecord) -> T {
+        self.value
+    }
+}
+
+impl<T: Texel> Texture<T> for Checker<T> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let p = self.scale * self.mapping.point(hit);
+        let parity = (p.x.floor() + p.y.floor() + p.z.floor()).rem_euclid(2.);
+        self.values[parity as usize]
+    }
+}
+
+impl<T: Texel> Texture<T> for Stripes<T> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let p = self.scale * self.mapping.point(hit);
+        self.values[p.x.floor().rem_euclid(2.) as usize]
+    }
+}
+
+impl<T: Texel> Gradient<T> {
+    // Sorts the stops by position; `None` without any, or with a NaN position.
+    pub fn new(mut stops: Vec<(f64, T)>, mapping: Mapping) -> Option<Self> {
+        if stops.is_empty() || stops.iter().any(|(at, _)| at.is_nan()) {
+            return None;
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Some(Self { stops, mapping })
+    }
+    pub fn stops(&self) -> &[(f64, T)] {
+        &self.stops
+    }
+}
+
+impl<T: Texel> Texture<T> for Gradient<T> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let x = self.mapping.point(hit).x;
+        let next = self.stops.partition_point(|&(at, _)| at <= x);
+        match (next.checked_sub(1), self.stops.get(next)) {
+            (Some(prev), Some(&(b, hi))) => {
+                let (a, lo) = self.stops[prev];
+                let t = (x - a) / (b - a);
+                lo * (1. - t) + hi * t
+            }
+            (Some(prev), None) => self.stops[prev].1,
+            (None, _) => self.stops[0].1,
+        }
+    }
+}
+
+impl<P: Pixel, const W: usize, const H: usize> ImageTexture<P, W, H> {
+    // Longest axis allowed for an EWA footprint relative to its shortest.
+    pub const MAX_ANISOTROPY: f64 = 8.;
+
+    // The trilinear and EWA filters get a box-filtered pyramid; `with_mips`
+    // rebuilds it with another filter.
+    pub fn new(image: Arc<Image<P, W, H>>, filter: Filter, address: Address) -> Self {
+        let texture = Self {
+            image,
+            mips: Arc::new([]),
+            filter,
+            address: [address; 2],
+        };
+        match filter {
+            Filter::Trilinear | Filter::Ewa => texture.with_mips(Downsample::Box),
+            _ => texture,
+        }
+    }
+    pub fn with_mips(self, downsample: Downsample) -> Self {
+        Self {
+            mips: self.image.mipmap(downsample).into(),
+            ..self
+        }
+    }
+    pub fn levels(&self) -> usize {
+        self.mips.len() + 1
+    }
+    fn dims(&self, level: usize) -> [usize; 2] {
+        match level {
+            0 => [W, H],
+            _ => self.mips[level - 1].dims(),
+        }
+    }
+    fn texel<T: Texel>(&self, level: usize, [i, j]: [isize; 2]) -> T {
+        let [w, h] = self.dims(level);
+        let p = [self.address[0].resolve(i, w), self.address[1].resolve(j, h)];
+        T::from_pixel(match level {
+            0 => self.image[p],
+            _ => self.mips[level - 1][p],
+        })
+    }
+    // Texel coordinates at a level, with `v` pointing up the image so row 0
+    // is at v = 1.
+    fn coords(&self, level: usize, [u, v]: [f64; 2]) -> [f64; 2] {
+        let [w, h] = self.dims(level);
+        [u * w as f64, (1. - v) * h as f64]
+    }
+    fn bilinear<T: Texel>(&self, level: usize, uv: [f64; 2]) -> T {
+        let [x, y] = self.coords(level, uv).map(|c| c - 0.5);
+        let (i, j) = (x.floor() as isize, y.floor() as isize);
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+        let row = |j: isize| {
+            self.texel::<T>(level, [i, j]) * (1. - fx) + self.texel::<T>(level, [i + 1, j]) * fx
+        };
+        row(j) * (1. - fy) + row(j + 1) * fy
+    }
+    fn trilinear<T: Texel>(&self, uv: [f64; 2], lod: f64) -> T {
+        let lod = lod.clamp(0., (self.levels() - 1) as f64);
+        let level = lod.floor() as usize;
+        let t = lod - level as f64;
+        if t == 0. {
+            self.bilinear(level, uv)
+        } else {
+            self.bilinear::<T>(level, uv) * (1. - t) + self.bilinear::<T>(level + 1, uv) * t
+        }
+    }
+    // Heckbert's elliptical weighted average with a Gaussian over the
+    // ellipse spanned by the two axes, given in texels of the full image.
+    fn ewa<T: Texel>(&self, level: usize, uv: [f64; 2], axes: [[f64; 2]; 2]) -> T {
+        let [w, h] = self.dims(level);
+        let scale = [w as f64 / W as f64, h as f64 / H as f64];
+        let [[du0, dv0], [du1, dv1]] = axes.map(|[x, y]| [x * scale[0], y * scale[1]]);
+        let [s, t] = self.coords(level, uv).map(|c| c - 0.5);
+
+        // the implicit ellipse A s² + B s t + C t² = 1, widened by a texel so
+        // it always covers one
+        let a = dv0 * dv0 + dv1 * dv1 + 1.;
+        let b = -2. * (du0 * dv0 + du1 * dv1);
+        let c = du0 * du0 + du1 * du1 + 1.;
+        let f = a * c - b * b / 4.;
+        let (a, b, c) = (a / f, b / f, c / f);
+        let det = 4. * a * c - b * b;
+        let (reach_s, reach_t) = (2. * (det * c).sqrt() / det, 2. * (det * a).sqrt() / det);
+
+        let mut sum: Option<T> = None;
+        let mut total = 0.;
+        for j in (t - reach_t).ceil() as isize..=(t + reach_t).floor() as isize {
+            for i in (s - reach_s).ceil() as isize..=(s + reach_s).floor() as isize {
+                let (ds, dt) = (i as f64 - s, j as f64 - t);
+                let r2 = a * ds * ds + b * ds * dt + c * dt * dt;
+                if r2 < 1. {
+                    let weight = (-2. * r2).exp() - (-2f64).exp();
+                    let texel = self.texel::<T>(level, [i, j]) * weight;
+                    sum = Some(sum.map_or(texel, |sum| sum + texel));
+                    total += weight;
+                }
+            }
+        }
+        match sum {
+            Some(sum) if total > 0. => sum * (1. / total),
+            _ => self.bilinear(level, uv),
+        }
+    }
+    pub fn lookup<T: Texel>(&self, uv: [f64; 2]) -> T {
+        match self.filter {
+            Filter::Nearest => {
+                let [x, y] = self.coords(0, uv);
+                self.texel(0, [x.floor() as isize, y.floor() as isize])
+            }
+            Filter::Bilinear | Filter::Trilinear | Filter::Ewa => self.bilinear(0, uv),
+            Filter::Bicubic => {
+                // Catmull-Rom weights for the four taps around the sample
+                fn weights(t: f64) -> [f64; 4] {
+                    let (t2, t3) = (t * t, t * t * t);
+                    [
+                        0.5 * (-t3 + 2. * t2 - t),
+                        0.5 * (3. * t3 - 5. * t2 + 2.),
+                        0.5 * (-3. * t3 + 4. * t2 + t),
+                        0.5 * (t3 - t2),
+                    ]
+                }
+
+                let [x, y] = self.coords(0, uv).map(|c| c - 0.5);
+                let (i, j) = (x.floor() as isize, y.floor() as isize);
+                let (wx, wy) = (weights(x - x.floor()), weights(y - y.floor()));
+                let row = |j: isize| {
+                    (1..4).fold(self.texel::<T>(0, [i - 1, j]) * wx[0], |acc, k| {
+                        acc + self.texel::<T>(0, [i - 1 + k as isize, j]) * wx[k]
+                    })
+                };
+                (1..4).fold(row(j - 1) * wy[0], |acc, k| {
+                    acc + row(j - 1 + k as isize) * wy[k]
+                })
+            }
+        }
+    }
+    // Filters over the footprint of a pixel, given as the change in `uv` per
+    // pixel in x and in y; only the trilinear and EWA filters use it.
+    pub fn lookup_footprint<T: Texel>(&self, uv: [f64; 2], footprint: [[f64; 2]; 2]) -> T {
+        let axes = footprint.map(|[du, dv]| [du * W as f64, -dv * H as f64]);
+        let length = |[x, y]: [f64; 2]| x.hypot(y);
+        match self.filter {
+            Filter::Trilinear => {
+                let width = length(axes[0]).max(length(axes[1]));
+                self.trilinear(uv, width.log2())
+            }
+            Filter::Ewa => {
+                let [mut minor, major] = if length(axes[0]) < length(axes[1]) {
+                    axes
+                } else {
+                    [axes[1], axes[0]]
+                };
+                // clamp the eccentricity, widening the ellipse rather than
+                // filtering along a needle over too many texels
+                let (minor_length, major_length) = (length(minor), length(major));
+                // under magnification the ellipse is smaller than a texel, and
+                // without mips a wide one covers too many
+                if major_length <= 1. || self.mips.is_empty() {
+                    return self.bilinear(0, uv);
+                }
+                if minor_length * Self::MAX_ANISOTROPY < major_length {
+                    let scale = major_length / (minor_length * Self::MAX_ANISOTROPY);
+                    minor = minor.map(|c| c * scale);
+                }
+                let lod = length(minor).log2().clamp(0., (self.levels() - 1) as f64);
+                let level = lod.floor() as usize;
+                let t = lod - level as f64;
+                if t == 0. || level + 1 == self.levels() {
+                    self.ewa(level, uv, [major, minor])
+                } else {
+                    self.ewa::<T>(level, uv, [major, minor]) * (1. - t)
+                        + self.ewa::<T>(level + 1, uv, [major, minor]) * t
+                }
+            }
+            _ => self.lookup(uv),
+        }
+    }
+}
+
+impl<T: Texel, P: Pixel, const W: usize, const H: usize> Texture<T> for ImageTexture<P, W, H> {
+    fn sample(&self, hit: &HitRecord) -> T {
+        let uv = hit.uv.unwrap_or_default();
+        match hit.uv_differentials() {
+            Some(footprint) => self.lookup_footprint(uv, footprint),
+            None => self.lookup(uv),
+        }
+    }
+}
+
+impl<P: Prop> Textured<P> {
+    pub const fn new(prop: P) -> Self {
+        Self {
+            prop,
+            colour: None,
+            ambient: None,
+            diffuse: None,
+            specular: None,
+            shininess: None,
+            normal: None,
+            bump: None,
+        }
+    }
+    pub fn with_colour(self, texture: impl Texture<Colour>) -> Self {
+        Self {
+            colour: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    pub fn with_ambient(self, texture: impl Texture<f64>) -> Self {
+        Self {
+            ambient: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    pub fn with_diffuse(self, texture: impl Texture<f64>) -> Self {
+        Self {
+            diffuse: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    pub fn with_specular(self, texture: impl Texture<f64>) -> Self {
+        Self {
+            specular: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    pub fn with_shininess(self, texture: impl Texture<f64>) -> Self {
+        Self {
+            shininess: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    // A tangent-space normal map, with x along dp/du, y along dp/dv and z out
+    // of the surface, encoded as colour = (n + 1) / 2.
+    pub fn with_normal_map(self, texture: impl Texture<Colour>) -> Self {
+        Self {
+            normal: Some(Arc::new(texture)),
+            ..self
+        }
+    }
+    // Displaces the surface along its normal by `scale` times the height.
+    pub fn with_bump(self, texture: impl Texture<f64>, scale: f64) -> Self {
+        Self {
+            bump: Some((Arc::new(texture), scale)),
+            ..self
+        }
+    }
+    // Surfaces without a parametrisation get an arbitrary frame; only solid
+    // textures, which ignore `uv`, bump meaningfully on those.
+    fn frame(hit: &HitRecord) -> [Vector; 2] {
+        let n = hit.shading_normal();
+        hit.tangents.unwrap_or_else(|| n.orthonormal())
+    }
+    fn normal_map(texture: &dyn Texture<Colour>, hit: &HitRecord) -> Vector {
+        let [dpdu, dpdv] = Self::frame(hit);
+        let n = hit.shading_normal();
+        let t = (dpdu - (dpdu * n) * n).norm();
+        let b = n ^ t;
+        let b = if b * dpdv < 0. { -b } else { b };
+        let Colour { r, g, b: z } = texture.sample(hit) * 2. - Colour::WHITE;
+        (r * t + g * b + z * n).norm()
+    }
+    // Blinn's bump mapping, differencing the height over a small step in u
+    // and v; the change in the geometric normal is neglected.
+    fn bump_map(texture: &dyn Texture<f64>, scale: f64, hit: &HitRecord) -> Vector {
+        const STEP: f64 = 1e-3;
+        let [dpdu, dpdv] = Self::frame(hit);
+        let [u, v] = hit.uv.unwrap_or_default();
+        let height = texture.sample(hit);
+        let slope = |offset: [f64; 2], dp: Vector| {
+            let shifted = HitRecord {
+                position: hit.position + STEP * dp,
+                uv: Some([u + offset[0], v + offset[1]]),
+                ..hit.clone()
+            };
+            scale * (texture.sample(&shifted) - height) / STEP
+        };
+        let n = hit.shading_normal();
+        let dpdu = dpdu + slope([STEP, 0.], dpdu) * n;
+        let dpdv = dpdv + slope([0., STEP], dpdv) * n;
+        let bumped = (dpdu ^ dpdv).norm();
+        if bumped * n < 0. { -bumped } else { bumped }
+    }
+    fn apply<'a>(&self, hit: HitRecord<'a>) -> HitRecord<'a> {
+        let colour = self.colour.as_ref().map(|texture| texture.sample(&hit));
+        let [ambient, diffuse, specular, shininess] = [
+            &self.ambient,
+            &self.diffuse,
+            &self.specular,
+            &self.shininess,
+        ]
+        .map(|texture| texture.as_ref().map(|texture| texture.sample(&hit)));
+        let material = hit.material;
+        // a normal map is applied in the frame of the bumped surface
+        let bumped = match &self.bump {
+            Some((texture, scale)) => Some(Self::bump_map(texture.as_ref(), *scale, &hit)),
+            None => hit.bumped,
+        };
+        let bumped = match &self.normal {
+            Some(texture) => Some(Self::normal_map(
+                texture.as_ref(),
+                &HitRecord {
+                    bumped,
+                    ..hit.clone()
+                },
+            )),
+            None => bumped,
+        };
+        HitRecord {
+            bumped,
+            material: Material {
+                colour: colour.map_or(material.colour, Colour::to_rgb),
+                ambient: ambient.unwrap_or(material.ambient),
+                diffuse: diffuse.unwrap_or(material.diffuse),
+                specular: specular.unwrap_or(material.specular),
+                shininess: shininess.unwrap_or(material.shininess),
+            },
+            ..hit
+        }
+    }
+}
+
+impl<P: Prop> Prop for Textured<P> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        self.prop.raycast(ray, eps).map(|hit| self.apply(hit))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.prop
+            .raycast_all(ray, eps)
+            .into_iter()
+            .map(|hit| self.apply(hit))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        self.prop.bounds()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::matrix::Matrix4;
 use std::ops::{
     Add, AddAssign, BitXor, BitXorAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg,
     Sub, SubAssign,
@@ -10,20 +11,54 @@ pub struct Vector {
     pub z: f64,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Ray {
     pub eye: Vector,
     pub dir: Vector,
+    pub time: f64,
+    pub differentials: Option<Differentials>,
+}
+
+// How the ray's eye and direction change per pixel step in x and in y.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Differentials {
+    pub eye: [Vector; 2],
+    pub dir: [Vector; 2],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
 }
 
 impl Ray {
     pub const fn new(eye: Vector, dir: Vector) -> Self {
-        Self { eye, dir }
+        Self {
+            eye,
+            dir,
+            time: 0.,
+            differentials: None,
+        }
     }
     pub const fn from_to(from: Vector, to: Vector) -> Self {
+        Self::new(from, to.sub(from))
+    }
+    pub const fn with_time(self, time: f64) -> Self {
+        Self { time, ..self }
+    }
+    pub const fn with_differentials(self, differentials: Differentials) -> Self {
         Self {
-            eye: from,
-            dir: to.sub(from),
+            differentials: Some(differentials),
+            ..self
         }
     }
     pub const fn at(&self, t: f64) -> Vector {
@@ -37,6 +72,349 @@ impl Ray {
     }
 }
 
+impl Quaternion {
+    pub const IDENTITY: Self = Self::new(1., 0., 0., 0.);
+
+    pub const fn new(w: f64, x: f64, y: f64, z: f64) -> Self {
+        Self { w, x, y, z }
+    }
+    pub const fn from_parts(w: f64, v: Vector) -> Self {
+        Self::new(w, v.x, v.y, v.z)
+    }
+    pub const fn vector(&self) -> Vector {
+        Vector::new(self.x, self.y, self.z)
+    }
+    pub fn from_axis_angle(axis: Vector, theta: f64) -> Self {
+        let (sin, cos) = (theta.to_radians() / 2.).sin_cos();
+        Self::from_parts(cos, sin * axis.norm())
+    }
+    pub fn from_euler([x, y, z]: [f64; 3]) -> Self {
+        Self::from_axis_angle(Vector::K, z)
+            * Self::from_axis_angle(Vector::J, y)
+            * Self::from_axis_angle(Vector::I, x)
+    }
+    pub fn from_to(from: Vector, to: Vector) -> Self {
+        let from = from.norm();
+        let to = to.norm();
+        let cos = from * to;
+        if cos < -1. + 1e-12 {
+            let axis = if from.x.abs() < 0.9 {
+                from ^ Vector::I
+            } else {
+                from ^ Vector::J
+            };
+            Self::from_parts(0., axis.norm())
+        } else {
+            Self::from_parts(1. + cos, from ^ to).norm()
+        }
+    }
+    pub fn from_matrix(m: &Matrix4) -> Self {
+        let m = &m.0;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0. {
+            let s = 2. * (trace + 1.).sqrt();
+            Self::new(
+                s / 4.,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2. * (1. + m[0][0] - m[1][1] - m[2][2]).sqrt();
+            Self::new(
+                (m[2][1] - m[1][2]) / s,
+                s / 4.,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = 2. * (1. + m[1][1] - m[0][0] - m[2][2]).sqrt();
+            Self::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = 2. * (1. + m[2][2] - m[0][0] - m[1][1]).sqrt();
+            Self::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.,
+            )
+        };
+        q.norm()
+    }
+    pub const fn add(self, rhs: Self) -> Self {
+        Self::new(
+            self.w + rhs.w,
+            self.x + rhs.x,
+            self.y + rhs.y,
+            self.z + rhs.z,
+        )
+    }
+    pub const fn sub(self, rhs: Self) -> Self {
+        Self::new(
+            self.w - rhs.w,
+            self.x - rhs.x,
+            self.y - rhs.y,
+            self.z - rhs.z,
+        )
+    }
+    pub const fn scale(self, rhs: f64) -> Self {
+        Self::new(self.w * rhs, self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+    pub const fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+    pub const fn neg(self) -> Self {
+        Self::new(-self.w, -self.x, -self.y, -self.z)
+    }
+    pub const fn conj(self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+    pub const fn dot(self, rhs: Self) -> f64 {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+    pub const fn sq(self) -> f64 {
+        self.dot(self)
+    }
+    pub fn abs(self) -> f64 {
+        self.sq().sqrt()
+    }
+    pub fn norm(self) -> Self {
+        self.scale(1. / self.abs())
+    }
+    pub fn inverse(self) -> Self {
+        self.conj().scale(1. / self.sq())
+    }
+    pub fn to_axis_angle(self) -> (Vector, f64) {
+        let q = if self.w < 0. { self.neg() } else { self };
+        let sin = q.vector().abs();
+        if sin < 1e-12 {
+            (Vector::I, 0.)
+        } else {
+            (q.vector() / sin, 2. * sin.atan2(q.w).to_degrees())
+        }
+    }
+    pub const fn to_matrix(self) -> Matrix4 {
+        let Self { w, x, y, z } = self;
+        Matrix4::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+    pub const fn rotate(self, v: Vector) -> Vector {
+        let u = self.vector();
+        let t = u.cross(v).mul(2.);
+        v.add(t.mul(self.w)).add(u.cross(t))
+    }
+    pub fn nlerp(self, rhs: Self, t: f64) -> Self {
+        let rhs = if self.dot(rhs) < 0. { rhs.neg() } else { rhs };
+        self.scale(1. - t).add(rhs.scale(t)).norm()
+    }
+    pub fn slerp(self, rhs: Self, t: f64) -> Self {
+        let mut cos = self.dot(rhs);
+        let rhs = if cos < 0. {
+            cos = -cos;
+            rhs.neg()
+        } else {
+            rhs
+        };
+        if cos > 1. - 1e-9 {
+            self.nlerp(rhs, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            self.scale(((1. - t) * theta).sin() / sin)
+                .add(rhs.scale((t * theta).sin() / sin))
+        }
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<Quaternion> for Matrix4 {
+    fn from(item: Quaternion) -> Self {
+        item.to_matrix()
+    }
+}
+
+impl Add for Quaternion {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self::Output {
+        self.add(rhs)
+    }
+}
+
+impl Sub for Quaternion {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.sub(rhs)
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.mul(rhs)
+    }
+}
+
+impl MulAssign for Quaternion {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = self.mul(rhs)
+    }
+}
+
+impl Mul<f64> for Quaternion {
+    type Output = Self;
+    fn mul(self, rhs: f64) -> Self::Output {
+        self.scale(rhs)
+    }
+}
+
+impl Mul<Vector> for Quaternion {
+    type Output = Vector;
+    fn mul(self, rhs: Vector) -> Self::Output {
+        self.rotate(rhs)
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Self;
+    fn neg(self) -> Self::Output {
+        self.neg()
+    }
+}
+
+impl Aabb {
+    pub const INFINITE: Self = Self {
+        min: Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        max: Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+    };
+    pub const EMPTY: Self = Self {
+        min: Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        max: Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+    };
+
+    pub const fn new(a: Vector, b: Vector) -> Self {
+        Self {
+            min: Vector::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Vector::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+    pub const fn around(centre: Vector, radius: f64) -> Self {
+        let r = Vector::new(radius.abs(), radius.abs(), radius.abs());
+        Self {
+            min: centre.sub(r),
+            max: centre.add(r),
+        }
+    }
+    pub const fn union(self, rhs: Self) -> Self {
+        Self {
+            min: Vector::new(
+                self.min.x.min(rhs.min.x),
+                self.min.y.min(rhs.min.y),
+                self.min.z.min(rhs.min.z),
+            ),
+            max: Vector::new(
+                self.max.x.max(rhs.max.x),
+                self.max.y.max(rhs.max.y),
+                self.max.z.max(rhs.max.z),
+            ),
+        }
+    }
+    pub const fn intersection(self, rhs: Self) -> Self {
+        Self {
+            min: Vector::new(
+                self.min.x.max(rhs.min.x),
+                self.min.y.max(rhs.min.y),
+                self.min.z.max(rhs.min.z),
+            ),
+            max: Vector::new(
+                self.max.x.min(rhs.max.x),
+                self.max.y.min(rhs.max.y),
+                self.max.z.min(rhs.max.z),
+            ),
+        }
+    }
+    pub const fn include(self, point: Vector) -> Self {
+        self.union(Self {
+            min: point,
+            max: point,
+        })
+    }
+    pub const fn translate(self, offset: Vector) -> Self {
+        Self {
+            min: self.min.add(offset),
+            max: self.max.add(offset),
+        }
+    }
+    pub const fn centre(&self) -> Vector {
+        self.min.add(self.max).div(2.)
+    }
+    pub const fn extent(&self) -> Vector {
+        self.max.sub(self.min)
+    }
+    pub const fn contains(&self, point: Vector) -> bool {
+        self.min.x <= point.x
+            && point.x <= self.max.x
+            && self.min.y <= point.y
+            && point.y <= self.max.y
+            && self.min.z <= point.z
+            && point.z <= self.max.z
+    }
+    pub fn hit(&self, ray: Ray, eps: f64) -> Option<[f64; 2]> {
+        let mut tmin = eps;
+        let mut tmax = f64::INFINITY;
+        for axis in [Vector::X, Vector::Y, Vector::Z] {
+            let inv = 1. / ray.dir[axis];
+            let t1 = (self.min[axis] - ray.eye[axis]) * inv;
+            let t2 = (self.max[axis] - ray.eye[axis]) * inv;
+            let (t1, t2) = if inv < 0. { (t2, t1) } else { (t1, t2) };
+            // NaN arises from 0 * inf when the ray lies on a slab plane; treat it as unbounded
+            if t1 > tmin {
+                tmin = t1;
+            }
+            if t2 < tmax {
+                tmax = t2;
+            }
+            if tmin > tmax {
+                return None;
+            }
+        }
+        Some([tmin, tmax])
+    }
+}
+
 impl Vector {
     pub const I: Self = Self::new(1., 0., 0.);
     pub const J: Self = Self::new(0., 1., 0.);
@@ -156,6 +534,16 @@ impl Vector {
     pub fn norm(self) -> Self {
         self / self.abs()
     }
+    pub fn orthonormal(self) -> [Self; 2] {
+        let n = self.norm();
+        let sign = 1f64.copysign(n.z);
+        let a = -1. / (sign + n.z);
+        let b = n.x * n.y * a;
+        [
+            Self::new(1. + sign * n.x * n.x * a, sign * b, -sign * n.x),
+            Self::new(b, sign + n.y * n.y * a, -n.y),
+        ]
+    }
     pub fn rotate_on_axis(mut self, axis: usize, theta: f64) -> Self {
         let theta = theta.to_radians();
         let cos = theta.cos();
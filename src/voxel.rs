@@ -0,0 +1,356 @@
+use crate::pixel::Rgb;
+use crate::prop::{HitRecord, Material, Prop};
+use crate::vector::{Aabb, Ray, Vector};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+
+pub trait Voxels: 'static + std::fmt::Debug {
+    fn get(&self, index: [isize; 3]) -> u8;
+    fn extent(&self) -> [[isize; 3]; 2];
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Dense {
+    size: [usize; 3],
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Chunked {
+    chunks: HashMap<[isize; 3], Box<[u8; Chunked::VOLUME]>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct VoxelGrid<V: Voxels> {
+    pub voxels: V,
+    pub origin: Vector,
+    pub size: f64,
+    pub palette: Vec<Material>,
+}
+
+impl Dense {
+    pub fn new(size: [usize; 3]) -> Self {
+        let len = size
+            .iter()
+            .try_fold(1usize, |n, &s| n.checked_mul(s))
+            .expect("voxel grid too large");
+        Self {
+            size,
+            data: vec![0; len],
+        }
+    }
+    pub const fn size(&self) -> [usize; 3] {
+        self.size
+    }
+    fn offset(&self, [x, y, z]: [isize; 3]) -> Option<usize> {
+        let [w, h, d] = self.size.map(|s| s as isize);
+        ((0..w).contains(&x) && (0..h).contains(&y) && (0..d).contains(&z))
+            .then(|| ((z * h + y) * w + x) as usize)
+    }
+    pub fn set(&mut self, index: [isize; 3], value: u8) {
+        if let Some(offset) = self.offset(index) {
+            self.data[offset] = value;
+        }
+    }
+}
+
+impl Voxels for Dense {
+    fn get(&self, index: [isize; 3]) -> u8 {
+        self.offset(index).map_or(0, |offset| self.data[offset])
+    }
+    fn extent(&self) -> [[isize; 3]; 2] {
+        [[0; 3], self.size.map(|s| s as isize)]
+    }
+}
+
+impl Chunked {
+    pub const SIZE: isize = 16;
+    const VOLUME: usize = (Self::SIZE * Self::SIZE * Self::SIZE) as usize;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn split(index: [isize; 3]) -> ([isize; 3], usize) {
+        let chunk = index.map(|c| c.div_euclid(Self::SIZE));
+        let [x, y, z] = index.map(|c| c.rem_euclid(Self::SIZE));
+        (chunk, ((z * Self::SIZE + y) * Self::SIZE + x) as usize)
+    }
+    pub fn set(&mut self, index: [isize; 3], value: u8) {
+        let (chunk, offset) = Self::split(index);
+        if value == 0 {
+            if let Some(data) = self.chunks.get_mut(&chunk) {
+                data[offset] = 0;
+                if data.iter().all(|&v| v == 0) {
+                    self.chunks.remove(&chunk);
+                }
+            }
+        } else {
+            self.chunks
+                .entry(chunk)
+                .or_insert_with(|| Box::new([0; Self::VOLUME]))[offset] = value;
+        }
+    }
+    pub fn chunks(&self) -> usize {
+        self.chunks.len()
+    }
+}
+
+impl Voxels for Chunked {
+    fn get(&self, index: [isize; 3]) -> u8 {
+        let (chunk, offset) = Self::split(index);
+        self.chunks.get(&chunk).map_or(0, |data| data[offset])
+    }
+    fn extent(&self) -> [[isize; 3]; 2] {
+        if self.chunks.is_empty() {
+            return [[0; 3]; 2];
+        }
+        self.chunks
+            .keys()
+            .fold([[isize::MAX; 3], [isize::MIN; 3]], |[min, max], chunk| {
+                [
+                    [0, 1, 2].map(|i| min[i].min(chunk[i] * Self::SIZE)),
+                    [0, 1, 2].map(|i| max[i].max((chunk[i] + 1) * Self::SIZE)),
+                ]
+            })
+    }
+}
+
+impl<V: Voxels> VoxelGrid<V> {
+    // What voxels are drawn with when the palette is empty.
+    pub const UNPAINTED: Material = Material {
+        colour: Rgb {
+            r: 0xff,
+            g: 0xff,
+            b: 0xff,
+        },
+        ambient: 0.1,
+        diffuse: 0.9,
+        specular: 0.,
+        shininess: 1.,
+    };
+
+    pub const fn new(voxels: V, origin: Vector, size: f64, palette: Vec<Material>) -> Self {
+        Self {
+            voxels,
+            origin,
+            size,
+            palette,
+        }
+    }
+    fn material(&self, index: u8) -> Material {
+        let last = self.palette.len().checked_sub(1);
+        last.map_or(Self::UNPAINTED, |last| {
+            self.palette[(index as usize).min(last)]
+        })
+    }
+    fn hit(&self, ray: Ray, t: f64, cell: [isize; 3], normal: Vector) -> HitRecord<'_> {
+        HitRecord {
+            voxel: Some(cell),
+            ..HitRecord::new(self, ray, t, normal, self.material(self.voxels.get(cell)))
+        }
+    }
+    fn grid_bounds(&self) -> Aabb {
+        let [min, max] = self.voxels.extent();
+        Aabb::new(
+            Vector::from_array(min.map(|c| c as f64)),
+            Vector::from_array(max.map(|c| c as f64)),
+        )
+    }
+    // Visits the cells along the ray with the time and face normal it enters
+    // them by, ending with the first cell past the grid, until `visit` returns
+    // true. The flag marks a starting cell the eye is already inside.
+    fn walk(
+        &self,
+        ray: Ray,
+        eps: f64,
+        mut visit: impl FnMut(f64, [isize; 3], Vector, bool) -> bool,
+    ) {
+        let eye = (ray.eye - self.origin) / self.size;
+        let dir = ray.dir / self.size;
+        let bounds = self.grid_bounds();
+        let Some([t0, t1]) = bounds.hit(Ray { eye, dir, ..ray }, eps) else {
+            return;
+        };
+        let inside = bounds.contains(eye);
+
+        let start = eye + t0 * dir;
+        let mut cell = [0, 1, 2].map(|i| (start[i] + 1e-9 * dir[i].signum()).floor() as isize);
+        let step = [0, 1, 2].map(|i| dir[i].signum() as isize);
+        let delta = [0, 1, 2].map(|i| (1. / dir[i]).abs());
+        let mut next = [0, 1, 2].map(|i| {
+            if dir[i] == 0. {
+                f64::INFINITY
+            } else {
+                let boundary = cell[i] as f64 + if dir[i] > 0. { 1. } else { 0. };
+                (boundary - eye[i]) / dir[i]
+            }
+        });
+
+        let Some(mut axis) = [0, 1, 2]
+            .into_iter()
+            .filter(|&i| dir[i] != 0.)
+            .max_by(|&a, &b| {
+                let entry = |i: usize| {
+                    (cell[i] as f64 + if dir[i] > 0. { 0. } else { 1. } - eye[i]) / dir[i]
+                };
+                entry(a).total_cmp(&entry(b))
+            })
+        else {
+            return;
+        };
+        let mut t = t0;
+        let mut first = true;
+
+        loop {
+            let mut normal = Vector::default();
+            normal[axis] = -step[axis] as f64;
+            if visit(t, cell, normal, first && inside) || t > t1 {
+                return;
+            }
+            first = false;
+
+            axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            t = next[axis];
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+        }
+    }
+    fn trace(&self, ray: Ray, eps: f64) -> Option<(f64, [isize; 3], Vector)> {
+        let mut hit = None;
+        self.walk(ray, eps, |t, cell, normal, start| {
+            if self.voxels.get(cell) != 0 && !start {
+                hit = Some((t, cell, normal));
+            }
+            hit.is_some()
+        });
+        hit
+    }
+    // Where the ray enters and leaves filled space, with the voxel on the
+    // filled side.
+    fn crossings(&self, ray: Ray, eps: f64) -> Vec<(f64, [isize; 3], Vector)> {
+        let mut crossings = Vec::new();
+        let mut previous = None;
+        self.walk(ray, eps, |t, cell, normal, start| {
+            let filled = self.voxels.get(cell) != 0;
+            match previous {
+                Some(last) if filled != (self.voxels.get(last) != 0) => {
+                    crossings.push(if filled {
+                        (t, cell, normal)
+                    } else {
+                        (t, last, -normal)
+                    });
+                }
+                None if filled && !start => crossings.push((t, cell, normal)),
+                _ => (),
+            }
+            previous = Some(cell);
+            false
+        });
+        crossings
+    }
+}
+
+impl VoxelGrid<Dense> {
+    pub fn from_vox(buf: &[u8], origin: Vector, size: f64, template: Material) -> Result<Self> {
+        fn invalid(msg: &str) -> Error {
+            Error::new(ErrorKind::InvalidData, format!("invalid VOX: {msg}"))
+        }
+        fn u32_at(buf: &[u8], pos: usize) -> Result<usize> {
+            buf.get(pos..pos + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .ok_or_else(|| invalid("unexpected end of file"))
+        }
+
+        if buf.get(..4) != Some(b"VOX ") {
+            return Err(invalid("bad magic number"));
+        }
+        if buf.get(8..12) != Some(b"MAIN") {
+            return Err(invalid("missing MAIN chunk"));
+        }
+
+        let mut dims = None;
+        let mut voxels = None;
+        let mut rgba = None;
+
+        let mut pos = 20 + u32_at(buf, 12)?;
+        while pos + 12 <= buf.len() {
+            let id = &buf[pos..pos + 4];
+            let content = u32_at(buf, pos + 4)?;
+            let children = u32_at(buf, pos + 8)?;
+            let body = buf
+                .get(pos + 12..pos + 12 + content)
+                .ok_or_else(|| invalid("truncated chunk"))?;
+            match id {
+                b"SIZE" if dims.is_none() => {
+                    dims = Some([u32_at(body, 0)?, u32_at(body, 4)?, u32_at(body, 8)?]);
+                }
+                b"XYZI" if voxels.is_none() => {
+                    let n = u32_at(body, 0)?;
+                    voxels = Some(
+                        body.get(4..4 + 4 * n)
+                            .ok_or_else(|| invalid("truncated XYZI chunk"))?,
+                    );
+                }
+                b"RGBA" => rgba = Some(body),
+                _ => (),
+            }
+            pos += 12 + content + children;
+        }
+
+        let [sx, sy, sz] = dims.ok_or_else(|| invalid("missing SIZE chunk"))?;
+        let xyzi = voxels.ok_or_else(|| invalid("missing XYZI chunk"))?;
+
+        // MagicaVoxel models are at most 256 voxels along each axis
+        if [sx, sy, sz].iter().any(|&s| !(1..=256).contains(&s))
+            || sx.checked_mul(sy).and_then(|n| n.checked_mul(sz)).is_none()
+        {
+            return Err(invalid("SIZE out of range"));
+        }
+
+        // MagicaVoxel is z-up; swap to y-up and mirror to keep handedness
+        let mut dense = Dense::new([sx, sz, sy]);
+        for v in xyzi.chunks_exact(4) {
+            let [x, y, z] = [v[0], v[1], v[2]].map(usize::from);
+            if x >= sx || y >= sy || z >= sz {
+                return Err(invalid("voxel outside SIZE"));
+            }
+            dense.set([x, z, sy - 1 - y].map(|c| c as isize), v[3]);
+        }
+
+        let palette = (0..256)
+            .map(|i| match rgba {
+                Some(rgba) if i > 0 && rgba.len() >= 4 * i => Material {
+                    colour: Rgb {
+                        r: rgba[4 * (i - 1)],
+                        g: rgba[4 * (i - 1) + 1],
+                        b: rgba[4 * (i - 1) + 2],
+                    },
+                    ..template
+                },
+                _ => template,
+            })
+            .collect();
+
+        Ok(Self::new(dense, origin, size, palette))
+    }
+}
+
+impl<V: Voxels> Prop for VoxelGrid<V> {
+    fn raycast(&self, ray: Ray, eps: f64) -> Option<HitRecord<'_>> {
+        let (t, cell, normal) = self.trace(ray, eps)?;
+        Some(self.hit(ray, t, cell, normal))
+    }
+    fn raycast_all(&self, ray: Ray, eps: f64) -> Vec<HitRecord<'_>> {
+        self.crossings(ray, eps)
+            .into_iter()
+            .map(|(t, cell, normal)| self.hit(ray, t, cell, normal))
+            .collect()
+    }
+    fn bounds(&self) -> Aabb {
+        let bounds = self.grid_bounds();
+        Aabb::new(
+            self.origin + self.size * bounds.min,
+            self.origin + self.size * bounds.max,
+        )
+    }
+}